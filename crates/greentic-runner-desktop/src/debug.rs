@@ -0,0 +1,442 @@
+//! Step debugger for desktop runs, driven by one JSON message per line.
+//!
+//! Commands: `{"command": "break", "nodes": [...]}`, `clear`, `continue`, `step`, `state`,
+//! `{"command": "set", "path": "/nodes/lookup/payload/name", "value": ...}` and `abort`.
+//! Events: `stopped` (reason, node and execution state), `state` and `error`. State paths are
+//! JSON pointers into `{"input": ..., "nodes": {...}, "egress": [...]}`.
+
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use anyhow::{Result, anyhow};
+use greentic_runner_host::runner::engine::{DebugStop, ExecutionState, FlowDebugger};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{Mutex as AsyncMutex, mpsc};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum DebugCommand {
+    /// Stop before these node ids, in any flow.
+    Break {
+        nodes: Vec<String>,
+    },
+    /// Remove every breakpoint.
+    Clear,
+    Continue,
+    /// Run the current node and stop before the next one.
+    Step,
+    /// Report the execution state of the paused flow.
+    State,
+    /// Replace (or add) the value at a JSON pointer in the paused flow's state.
+    Set {
+        path: String,
+        value: Value,
+    },
+    Abort,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DebugEvent {
+    Stopped {
+        reason: StopReason,
+        flow_id: String,
+        node_id: String,
+        component: String,
+        state: Value,
+    },
+    State {
+        state: Value,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    Entry,
+    Breakpoint,
+    Step,
+}
+
+/// A [`FlowDebugger`] that pauses at breakpoints and steps, waiting for [`DebugCommand`]s while
+/// paused. Pass it as [`RunOptions::debugger`](crate::RunOptions::debugger).
+pub struct DebugSession {
+    breakpoints: Mutex<HashSet<String>>,
+    stop_on_entry: AtomicBool,
+    stepping: AtomicBool,
+    commands: AsyncMutex<mpsc::UnboundedReceiver<DebugCommand>>,
+    events: mpsc::UnboundedSender<DebugEvent>,
+}
+
+/// The editor's end of a [`DebugSession`].
+pub struct DebugClient {
+    pub commands: mpsc::UnboundedSender<DebugCommand>,
+    pub events: mpsc::UnboundedReceiver<DebugEvent>,
+}
+
+impl DebugSession {
+    pub fn new(
+        stop_on_entry: bool,
+        breakpoints: impl IntoIterator<Item = String>,
+    ) -> (Arc<Self>, DebugClient) {
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let session = Arc::new(Self {
+            breakpoints: Mutex::new(breakpoints.into_iter().collect()),
+            stop_on_entry: AtomicBool::new(stop_on_entry),
+            stepping: AtomicBool::new(false),
+            commands: AsyncMutex::new(command_rx),
+            events: event_tx,
+        });
+        let client = DebugClient {
+            commands: command_tx,
+            events: event_rx,
+        };
+        (session, client)
+    }
+
+    /// A session driven over stdin (commands) and stdout (events). Lines that are not valid
+    /// commands are answered with an `error` event.
+    pub fn stdio(stop_on_entry: bool, breakpoints: impl IntoIterator<Item = String>) -> Arc<Self> {
+        let (session, client) = Self::new(stop_on_entry, breakpoints);
+        let DebugClient {
+            commands,
+            mut events,
+        } = client;
+        let errors = session.events.clone();
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(&line) {
+                    Ok(command) => {
+                        if commands.send(command).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        let _ = errors.send(DebugEvent::Error {
+                            message: format!("invalid command: {err}"),
+                        });
+                    }
+                }
+            }
+        });
+        thread::spawn(move || {
+            while let Some(event) = events.blocking_recv() {
+                let mut stdout = std::io::stdout().lock();
+                let written = serde_json::to_writer(&mut stdout, &event)
+                    .map_err(std::io::Error::from)
+                    .and_then(|()| writeln!(stdout))
+                    .and_then(|()| stdout.flush());
+                if written.is_err() {
+                    break;
+                }
+            }
+        });
+        session
+    }
+
+    fn send(&self, event: DebugEvent) {
+        let _ = self.events.send(event);
+    }
+
+    fn should_stop(&self, node_id: &str) -> Option<StopReason> {
+        if self.stop_on_entry.swap(false, Ordering::SeqCst) {
+            Some(StopReason::Entry)
+        } else if self.stepping.load(Ordering::SeqCst) {
+            Some(StopReason::Step)
+        } else if self.breakpoints.lock().contains(node_id) {
+            Some(StopReason::Breakpoint)
+        } else {
+            None
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl FlowDebugger for DebugSession {
+    async fn before_node(&self, stop: &DebugStop<'_>, state: &mut ExecutionState) -> Result<()> {
+        // Held for the whole pause, so parallel branches stop one at a time.
+        let mut commands = self.commands.lock().await;
+        // Commands sent while the run was going: breakpoints apply now, `step` pauses here.
+        while let Ok(command) = commands.try_recv() {
+            match command {
+                DebugCommand::Break { nodes } => self.breakpoints.lock().extend(nodes),
+                DebugCommand::Clear => self.breakpoints.lock().clear(),
+                DebugCommand::Step => self.stepping.store(true, Ordering::SeqCst),
+                DebugCommand::Continue => {}
+                DebugCommand::Abort => return Err(anyhow!("run aborted from the debugger")),
+                DebugCommand::State | DebugCommand::Set { .. } => self.send(DebugEvent::Error {
+                    message: "the run is not paused".into(),
+                }),
+            }
+        }
+        let Some(reason) = self.should_stop(stop.node_id) else {
+            return Ok(());
+        };
+        self.send(DebugEvent::Stopped {
+            reason,
+            flow_id: stop.flow_id.to_string(),
+            node_id: stop.node_id.to_string(),
+            component: stop.node.component.clone(),
+            state: snapshot(state),
+        });
+        loop {
+            let Some(command) = commands.recv().await else {
+                // The client went away; let the run finish on its own.
+                self.stepping.store(false, Ordering::SeqCst);
+                self.breakpoints.lock().clear();
+                return Ok(());
+            };
+            match command {
+                DebugCommand::Continue => {
+                    self.stepping.store(false, Ordering::SeqCst);
+                    return Ok(());
+                }
+                DebugCommand::Step => {
+                    self.stepping.store(true, Ordering::SeqCst);
+                    return Ok(());
+                }
+                DebugCommand::State => self.send(DebugEvent::State {
+                    state: snapshot(state),
+                }),
+                DebugCommand::Set { path, value } => match set_state(state, &path, value) {
+                    Ok(()) => self.send(DebugEvent::State {
+                        state: snapshot(state),
+                    }),
+                    Err(err) => self.send(DebugEvent::Error {
+                        message: format!("{err:#}"),
+                    }),
+                },
+                DebugCommand::Break { nodes } => self.breakpoints.lock().extend(nodes),
+                DebugCommand::Clear => self.breakpoints.lock().clear(),
+                DebugCommand::Abort => return Err(anyhow!("run aborted from the debugger")),
+            }
+        }
+    }
+}
+
+fn snapshot(state: &ExecutionState) -> Value {
+    serde_json::to_value(state).unwrap_or(Value::Null)
+}
+
+/// Write `value` at the JSON pointer `path`, adding the last segment to an object if missing.
+/// The state is left untouched when the result is not a valid execution state.
+fn set_state(state: &mut ExecutionState, path: &str, value: Value) -> Result<()> {
+    let mut edited = snapshot(state);
+    if let Some(slot) = edited.pointer_mut(path) {
+        *slot = value;
+    } else {
+        let (parent, key) = path
+            .rsplit_once('/')
+            .ok_or_else(|| anyhow!("{path} is not a JSON pointer"))?;
+        let key = key.replace("~1", "/").replace("~0", "~");
+        edited
+            .pointer_mut(parent)
+            .and_then(Value::as_object_mut)
+            .ok_or_else(|| anyhow!("no object at {parent}"))?
+            .insert(key, value);
+    }
+    *state = serde_json::from_value(edited).map_err(|err| anyhow!("edit rejected: {err}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use greentic_flow::ir::NodeIR;
+    use serde_json::json;
+    use std::future::Future;
+
+    fn state() -> ExecutionState {
+        serde_json::from_value(json!({
+            "input": { "name": "ada" },
+            "nodes": {
+                "lookup": { "ok": true, "payload": { "id": 7 }, "meta": null }
+            },
+            "egress": []
+        }))
+        .unwrap()
+    }
+
+    /// Reach `node_id` while `editor` plays the client's side of the pause.
+    async fn at_node(
+        session: &DebugSession,
+        node_id: &str,
+        state: &mut ExecutionState,
+        editor: impl Future<Output = ()>,
+    ) -> Result<()> {
+        let node = NodeIR {
+            component: "qa.process".into(),
+            payload_expr: json!({}),
+            routes: Vec::new(),
+        };
+        let stop = DebugStop {
+            flow_id: "main",
+            node_id,
+            node: &node,
+        };
+        let (result, ()) = tokio::join!(session.before_node(&stop, state), editor);
+        result
+    }
+
+    fn stop_reason(event: Option<DebugEvent>, node: &str) -> StopReason {
+        match event {
+            Some(DebugEvent::Stopped {
+                reason, node_id, ..
+            }) if node_id == node => reason,
+            other => panic!("expected a stop at {node}, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn stops_at_breakpoints_and_steps() {
+        let (session, mut client) = DebugSession::new(false, ["lookup".to_string()]);
+        let mut state = state();
+
+        at_node(&session, "start", &mut state, async {})
+            .await
+            .unwrap();
+        assert!(client.events.try_recv().is_err());
+
+        at_node(&session, "lookup", &mut state, async {
+            let reason = stop_reason(client.events.recv().await, "lookup");
+            assert_eq!(reason, StopReason::Breakpoint);
+            client.commands.send(DebugCommand::Step).unwrap();
+        })
+        .await
+        .unwrap();
+        at_node(&session, "reply", &mut state, async {
+            let reason = stop_reason(client.events.recv().await, "reply");
+            assert_eq!(reason, StopReason::Step);
+            client.commands.send(DebugCommand::Continue).unwrap();
+        })
+        .await
+        .unwrap();
+
+        at_node(&session, "done", &mut state, async {})
+            .await
+            .unwrap();
+        assert!(client.events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn stops_on_entry_and_aborts() {
+        let (session, mut client) = DebugSession::new(true, Vec::new());
+        let mut state = state();
+        let err = at_node(&session, "start", &mut state, async {
+            let reason = stop_reason(client.events.recv().await, "start");
+            assert_eq!(reason, StopReason::Entry);
+            client.commands.send(DebugCommand::Abort).unwrap();
+        })
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("aborted"));
+    }
+
+    #[tokio::test]
+    async fn set_edits_the_paused_state() {
+        let (session, mut client) = DebugSession::new(true, Vec::new());
+        let mut state = state();
+        at_node(&session, "start", &mut state, async {
+            client.events.recv().await.unwrap();
+            let set = |path: &str, value: Value| {
+                client
+                    .commands
+                    .send(DebugCommand::Set {
+                        path: path.into(),
+                        value,
+                    })
+                    .unwrap();
+            };
+            set("/input/name", json!("grace"));
+            set("/nodes/lookup/payload/email", json!("g@example.com"));
+            // Not an execution state, and no object to insert into.
+            set("/nodes/lookup/ok", json!("yes"));
+            set("/missing/key", json!(1));
+            client.commands.send(DebugCommand::Continue).unwrap();
+
+            let replaced = client.events.recv().await.unwrap();
+            assert!(
+                matches!(&replaced, DebugEvent::State { state } if state["input"]["name"] == "grace")
+            );
+            let inserted = client.events.recv().await.unwrap();
+            assert!(matches!(
+                &inserted,
+                DebugEvent::State { state }
+                    if state["nodes"]["lookup"]["payload"]["email"] == "g@example.com"
+            ));
+            for expected in ["edit rejected", "no object at /missing"] {
+                match client.events.recv().await {
+                    Some(DebugEvent::Error { message }) => {
+                        assert!(message.contains(expected), "{message}")
+                    }
+                    other => panic!("expected an error, got {other:?}"),
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        let edited = snapshot(&state);
+        assert_eq!(edited["input"]["name"], "grace");
+        assert_eq!(edited["nodes"]["lookup"]["ok"], true);
+        assert_eq!(
+            edited["nodes"]["lookup"]["payload"],
+            json!({ "id": 7, "email": "g@example.com" })
+        );
+    }
+
+    #[tokio::test]
+    async fn commands_sent_while_running_apply_at_the_next_node() {
+        let (session, mut client) = DebugSession::new(false, Vec::new());
+        let mut state = state();
+        let commands = &client.commands;
+        commands
+            .send(DebugCommand::Break {
+                nodes: vec!["reply".into()],
+            })
+            .unwrap();
+        commands.send(DebugCommand::State).unwrap();
+        commands.send(DebugCommand::Continue).unwrap();
+
+        at_node(&session, "start", &mut state, async {})
+            .await
+            .unwrap();
+        match client.events.try_recv() {
+            Ok(DebugEvent::Error { message }) => assert!(message.contains("not paused")),
+            other => panic!("expected an error, got {other:?}"),
+        }
+        assert!(client.events.try_recv().is_err());
+
+        at_node(&session, "reply", &mut state, async {
+            let reason = stop_reason(client.events.recv().await, "reply");
+            assert_eq!(reason, StopReason::Breakpoint);
+            client.commands.send(DebugCommand::Clear).unwrap();
+            client.commands.send(DebugCommand::Continue).unwrap();
+        })
+        .await
+        .unwrap();
+
+        // A queued `step` pauses at the very next node.
+        client.commands.send(DebugCommand::Step).unwrap();
+        at_node(&session, "done", &mut state, async {
+            let reason = stop_reason(client.events.recv().await, "done");
+            assert_eq!(reason, StopReason::Step);
+            client.commands.send(DebugCommand::Continue).unwrap();
+        })
+        .await
+        .unwrap();
+    }
+}
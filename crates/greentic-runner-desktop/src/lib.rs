@@ -1,11 +1,18 @@
+pub mod debug;
+
 use anyhow::{Context, Result, anyhow};
 use greentic_pack::reader::{PackLoad, open_pack};
 use greentic_runner_host::RunnerWasiPolicy;
 use greentic_runner_host::config::{
-    HostConfig, McpConfig, McpRetryConfig, RateLimits, SecretsPolicy, WebhookPolicy,
+    DEFAULT_MAX_NODE_VISITS, FlowLimits, FlowTimeouts, HostConfig, JournalConfig, McpConfig,
+    McpRetryConfig, RateLimits, ResumePolicy, SecretsPolicy, WebhookPolicy,
 };
 use greentic_runner_host::pack::{FlowDescriptor, PackMetadata, PackRuntime};
-use greentic_runner_host::runner::engine::{ExecutionObserver, FlowContext, FlowEngine, NodeEvent};
+use greentic_runner_host::runner::engine::{
+    DeadlineExceeded, ExecutionObserver, FlowContext, FlowDebugger, FlowEngine, NodeBudget,
+    NodeEvent,
+};
+use greentic_runner_host::runner::journal::Journal;
 pub use greentic_runner_host::runner::mocks::{
     HttpMock, HttpMockMode, KvMock, MocksConfig, SecretsMock, TelemetryMock, TimeMock, ToolsMock,
 };
@@ -21,7 +28,6 @@ use std::fs::{self, File};
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
 use time::OffsetDateTime;
 use time::format_description::well_known::Rfc3339;
 use tokio::runtime::Runtime;
@@ -65,6 +71,8 @@ pub struct DevProfile {
     pub user_id: String,
     pub max_node_wall_time_ms: u64,
     pub max_run_wall_time_ms: u64,
+    /// Node executions allowed per run before it is treated as a routing loop.
+    pub max_node_visits: u32,
 }
 
 impl Default for DevProfile {
@@ -75,6 +83,7 @@ impl Default for DevProfile {
             user_id: "developer".to_string(),
             max_node_wall_time_ms: 30_000,
             max_run_wall_time_ms: 600_000,
+            max_node_visits: DEFAULT_MAX_NODE_VISITS,
         }
     }
 }
@@ -111,6 +120,14 @@ pub struct RunOptions {
     pub mocks: MocksConfig,
     pub artifacts_dir: Option<PathBuf>,
     pub signing: SigningPolicy,
+    /// `journal.json` of an earlier run to replay instead of dispatching nodes.
+    pub replay: Option<PathBuf>,
+    /// Record the run's journal into `journal.json` in the artifacts directory. Ignored when
+    /// replaying.
+    pub record_journal: bool,
+    /// Pauses the run between nodes, e.g. a [`debug::DebugSession`]. The run deadline is
+    /// lifted while debugging so time spent paused does not fail the run.
+    pub debugger: Option<Arc<dyn FlowDebugger>>,
 }
 
 impl Default for RunOptions {
@@ -131,6 +148,9 @@ impl fmt::Debug for RunOptions {
             .field("mocks", &self.mocks)
             .field("artifacts_dir", &self.artifacts_dir)
             .field("signing", &self.signing)
+            .field("replay", &self.replay)
+            .field("record_journal", &self.record_journal)
+            .field("debugger", &self.debugger.is_some())
             .finish()
     }
 }
@@ -215,6 +235,9 @@ pub fn desktop_defaults() -> RunOptions {
         mocks: MocksConfig::default(),
         artifacts_dir: None,
         signing: SigningPolicy::DevOk,
+        replay: None,
+        record_journal: false,
+        debugger: None,
     }
 }
 
@@ -297,7 +320,8 @@ async fn run_pack_async(pack_path: &Path, opts: RunOptions) -> Result<RunResult>
 
     let engine = FlowEngine::new(vec![Arc::clone(&pack)], Arc::clone(&host_config))
         .await
-        .context("failed to prime flow engine")?;
+        .context("failed to prime flow engine")?
+        .with_state_store(Arc::clone(&state_store));
 
     let started_at = OffsetDateTime::now_utc();
     let tenant_str = host_config.tenant.clone();
@@ -305,6 +329,10 @@ async fn run_pack_async(pack_path: &Path, opts: RunOptions) -> Result<RunResult>
     let provider_id_owned = resolved_profile.provider_id.clone();
     let recorder_ref: &RunRecorder = &recorder;
     let mock_ref: &MockLayer = &mock_layer;
+    let journal = match &opts.replay {
+        Some(path) => Some(Journal::load(path)?),
+        None => opts.record_journal.then(Journal::recording),
+    };
     let ctx = FlowContext {
         tenant: &tenant_str,
         flow_id: &entry_flow_id,
@@ -312,10 +340,23 @@ async fn run_pack_async(pack_path: &Path, opts: RunOptions) -> Result<RunResult>
         tool: None,
         action: Some("run_pack"),
         session_id: Some(session_id_owned.as_str()),
+        user_id: Some(resolved_profile.user_id.as_str()),
         provider_id: Some(provider_id_owned.as_str()),
         retry_config: host_config.mcp_retry_config().into(),
         observer: Some(recorder_ref),
         mocks: Some(mock_ref),
+        deadline: if opts.debugger.is_some() {
+            None
+        } else {
+            host_config.timeouts.run_deadline()
+        },
+        node_timeout: host_config.timeouts.node_timeout(),
+        node_budget: NodeBudget::new(host_config.limits.max_node_visits),
+        resume_token: None,
+        egress: None,
+        cancel: Default::default(),
+        journal: journal.as_ref(),
+        debugger: opts.debugger.as_deref(),
     };
 
     let execution = engine.execute(ctx, opts.input.clone()).await;
@@ -333,6 +374,10 @@ async fn run_pack_async(pack_path: &Path, opts: RunOptions) -> Result<RunResult>
 
     let result = recorder.finalise(status, started_at, finished_at)?;
 
+    if let Some(journal) = journal.as_ref().filter(|journal| !journal.is_replay()) {
+        journal.save(&directories.root.join("journal.json"))?;
+    }
+
     let run_json_path = directories.root.join("run.json");
     fs::write(&run_json_path, serde_json::to_vec_pretty(&result)?)
         .with_context(|| format!("failed to write run summary {}", run_json_path.display()))?;
@@ -477,6 +522,7 @@ fn resolve_profile(profile: &Profile, ctx: &TenantContext) -> ResolvedProfile {
             provider_id: PROVIDER_ID_DEV.to_string(),
             max_node_wall_time_ms: dev.max_node_wall_time_ms,
             max_run_wall_time_ms: dev.max_run_wall_time_ms,
+            max_node_visits: dev.max_node_visits,
         },
     }
 }
@@ -513,6 +559,16 @@ fn build_host_config(profile: &ResolvedProfile, dirs: &RunDirectories) -> HostCo
         secrets_policy: SecretsPolicy::allow_all(),
         webhook_policy: WebhookPolicy::default(),
         timers: Vec::new(),
+        timeouts: FlowTimeouts {
+            node_ms: Some(profile.max_node_wall_time_ms),
+            run_ms: Some(profile.max_run_wall_time_ms),
+        },
+        limits: FlowLimits {
+            max_node_visits: profile.max_node_visits,
+        },
+        node_retries: HashMap::new(),
+        resume: ResumePolicy::default(),
+        journal: JournalConfig::default(),
     }
 }
 
@@ -526,6 +582,7 @@ struct ResolvedProfile {
     provider_id: String,
     max_node_wall_time_ms: u64,
     max_run_wall_time_ms: u64,
+    max_node_visits: u32,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -538,6 +595,9 @@ pub struct RunResult {
     pub finished_at_utc: String,
     pub status: RunStatus,
     pub node_summaries: Vec<NodeSummary>,
+    /// Node executions charged to the run's visit budget, sub-flows and repeats included.
+    #[serde(default)]
+    pub node_visits: u32,
     pub failures: BTreeMap<String, NodeFailure>,
     pub artifacts_dir: PathBuf,
 }
@@ -691,6 +751,7 @@ impl RunRecorder {
             finished_at_utc: finished,
             status: final_status,
             node_summaries: summaries,
+            node_visits: state.node_visits,
             failures,
             artifacts_dir: self.directories.root.clone(),
         })
@@ -782,6 +843,7 @@ impl RunRecorder {
         let (start_offset, _) = self.transcript.lock().write(&event_json)?;
 
         let mut state = self.state.lock();
+        state.node_visits = state.node_visits.max(event.visit);
         let node_key = event.node_id.to_string();
         if !state.order.iter().any(|id| id == &node_key) {
             state.order.push(node_key.clone());
@@ -789,7 +851,6 @@ impl RunRecorder {
         let entry = state.nodes.entry(node_key).or_insert_with(|| {
             NodeExecutionRecord::new(event.node.component.clone(), &self.directories)
         });
-        entry.start_instant = Some(Instant::now());
         entry.status = NodeStatus::Ok;
         entry.transcript_start = Some(start_offset);
         Ok(())
@@ -815,10 +876,8 @@ impl RunRecorder {
         self.transcript.lock().write(&event_json)?;
 
         let mut state = self.state.lock();
-        if let Some(entry) = state.nodes.get_mut(event.node_id)
-            && let Some(started) = entry.start_instant.take()
-        {
-            entry.duration_ms = Some(started.elapsed().as_millis() as u64);
+        if let Some(entry) = state.nodes.get_mut(event.node_id) {
+            entry.duration_ms = Some(event.elapsed().as_millis() as u64);
         }
         Ok(())
     }
@@ -826,12 +885,17 @@ impl RunRecorder {
     fn handle_node_error(
         &self,
         event: &NodeEvent<'_>,
-        error: &dyn std::error::Error,
+        error: &(dyn std::error::Error + 'static),
     ) -> Result<()> {
         let timestamp = OffsetDateTime::now_utc();
         let error_message = error.to_string();
+        let failure_code = if error.is::<DeadlineExceeded>() {
+            "timeout"
+        } else {
+            "component-failed"
+        };
         let error_json = json!({
-            "code": "component-failed",
+            "code": failure_code,
             "message": error_message,
             "details": {
                 "node": event.node_id,
@@ -856,11 +920,9 @@ impl RunRecorder {
         let mut state = self.state.lock();
         if let Some(entry) = state.nodes.get_mut(event.node_id) {
             entry.status = NodeStatus::Error;
-            if let Some(started) = entry.start_instant.take() {
-                entry.duration_ms = Some(started.elapsed().as_millis() as u64);
-            }
+            entry.duration_ms = Some(event.elapsed().as_millis() as u64);
             entry.transcript_error = Some(end_offset);
-            entry.failure_code = Some("component-failed".to_string());
+            entry.failure_code = Some(failure_code.to_string());
             entry.failure_message = Some(error_message.clone());
             entry.failure_details = Some(error_json);
             let log_path = self
@@ -889,7 +951,7 @@ impl ExecutionObserver for RunRecorder {
         }
     }
 
-    fn on_node_error(&self, event: &NodeEvent<'_>, error: &dyn std::error::Error) {
+    fn on_node_error(&self, event: &NodeEvent<'_>, error: &(dyn std::error::Error + 'static)) {
         if let Err(err) = self.handle_node_error(event, error) {
             warn!(node = event.node_id, error = %err, "failed to record node error");
         }
@@ -908,6 +970,7 @@ impl MockEventSink for RunRecorder {
 struct RunRecorderState {
     nodes: BTreeMap<String, NodeExecutionRecord>,
     order: Vec<String>,
+    node_visits: u32,
 }
 
 #[derive(Clone)]
@@ -921,7 +984,6 @@ struct NodeExecutionRecord {
     failure_code: Option<String>,
     failure_message: Option<String>,
     failure_details: Option<Value>,
-    start_instant: Option<Instant>,
 }
 
 impl NodeExecutionRecord {
@@ -936,7 +998,6 @@ impl NodeExecutionRecord {
             failure_code: None,
             failure_message: None,
             failure_details: None,
-            start_instant: None,
         }
     }
 }
@@ -3,8 +3,7 @@ use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
-#[cfg(feature = "mcp")]
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 #[cfg(feature = "mcp")]
@@ -23,6 +22,11 @@ pub struct HostConfig {
     pub secrets_policy: SecretsPolicy,
     pub webhook_policy: WebhookPolicy,
     pub timers: Vec<TimerBinding>,
+    pub timeouts: FlowTimeouts,
+    pub limits: FlowLimits,
+    pub node_retries: HashMap<String, HashMap<String, McpRetryConfig>>,
+    pub resume: ResumePolicy,
+    pub journal: JournalConfig,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -35,6 +39,17 @@ pub struct BindingsFile {
     pub rate_limits: RateLimits,
     #[serde(default)]
     pub timers: Vec<TimerBinding>,
+    #[serde(default)]
+    pub timeouts: FlowTimeouts,
+    #[serde(default)]
+    pub limits: FlowLimits,
+    /// Per-node retry overrides keyed by flow id, then node id; other nodes use `mcp.retry`.
+    #[serde(default)]
+    pub node_retries: HashMap<String, HashMap<String, McpRetryConfig>>,
+    #[serde(default)]
+    pub resume: ResumePolicy,
+    #[serde(default)]
+    pub journal: JournalConfig,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -95,6 +110,93 @@ pub struct WebhookBindingConfig {
     pub deny_paths: Vec<String>,
 }
 
+/// Wall-clock limits applied by the flow engine; unset values mean "no limit".
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FlowTimeouts {
+    #[serde(default)]
+    pub node_ms: Option<u64>,
+    #[serde(default)]
+    pub run_ms: Option<u64>,
+}
+
+/// Node executions allowed per run (sub-flows included) before the engine assumes a loop.
+pub const DEFAULT_MAX_NODE_VISITS: u32 = 1000;
+
+/// Execution budgets applied by the flow engine to every run.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlowLimits {
+    #[serde(default = "default_max_node_visits")]
+    pub max_node_visits: u32,
+}
+
+/// How an activity picks among its session's waiting flows, and how waits are resumed once
+/// the pack that saved their snapshot is replaced and no longer kept alive for them.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResumePolicy {
+    #[serde(default)]
+    pub select: WaitSelection,
+    #[serde(default)]
+    pub on_pack_change: PackChangePolicy,
+    /// Node renames applied to such snapshots, keyed by flow id, then old node id.
+    #[serde(default)]
+    pub migrations: HashMap<String, HashMap<String, String>>,
+    /// How long a replaced pack is kept alive for a session waiting without a timeout. Waits
+    /// with a timeout keep it until shortly after their wake-up.
+    #[serde(default = "default_pin_ttl_ms")]
+    pub pin_ttl_ms: u64,
+}
+
+impl Default for ResumePolicy {
+    fn default() -> Self {
+        Self {
+            select: WaitSelection::default(),
+            on_pack_change: PackChangePolicy::default(),
+            migrations: HashMap::new(),
+            pin_ttl_ms: default_pin_ttl_ms(),
+        }
+    }
+}
+
+fn default_pin_ttl_ms() -> u64 {
+    24 * 60 * 60 * 1000
+}
+
+/// Which waiting flow of a session an inbound activity resumes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitSelection {
+    /// Resume the wait of the flow the activity targets; otherwise start that flow.
+    #[default]
+    Flow,
+    /// Like `flow`, but when the targeted flow is not waiting, resume the session's most
+    /// recent wait in any flow.
+    Latest,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PackChangePolicy {
+    /// Apply `migrations`, then resume on the new pack if every node the snapshot points at
+    /// exists there; otherwise discard the snapshot and start the flow over.
+    #[default]
+    Migrate,
+    /// Discard the snapshot and start the flow over.
+    Restart,
+    /// Discard the snapshot and fail the activity.
+    Fail,
+}
+
+/// Execution journals of the host's pack flow runs, to replay production runs later.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct JournalConfig {
+    /// Record a journal for every run. Off by default.
+    #[serde(default)]
+    pub record: bool,
+    /// Directory for the journals; relative to the bindings file. Defaults to `journals`.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct TimerBinding {
     pub flow_id: String,
@@ -140,6 +242,11 @@ impl HostConfig {
             secrets_policy,
             webhook_policy,
             timers: bindings.timers.clone(),
+            timeouts: bindings.timeouts.clone(),
+            limits: bindings.limits.clone(),
+            node_retries: bindings.node_retries.clone(),
+            resume: bindings.resume.clone(),
+            journal: bindings.journal.clone(),
         })
     }
 
@@ -147,6 +254,22 @@ impl HostConfig {
         self.flow_type_bindings.get("messaging")
     }
 
+    /// Where run journals are written, or `None` unless `journal.record` is set.
+    pub fn journal_dir(&self) -> Option<PathBuf> {
+        if !self.journal.record {
+            return None;
+        }
+        let dir = self
+            .journal
+            .dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("journals"));
+        Some(match self.bindings_path.parent() {
+            Some(base) if dir.is_relative() => base.join(dir),
+            _ => dir,
+        })
+    }
+
     pub fn mcp_retry_config(&self) -> McpRetryConfig {
         self.mcp.retry.clone().unwrap_or_default()
     }
@@ -212,6 +335,30 @@ fn default_messaging_burst() -> u32 {
     20
 }
 
+impl Default for FlowLimits {
+    fn default() -> Self {
+        Self {
+            max_node_visits: default_max_node_visits(),
+        }
+    }
+}
+
+fn default_max_node_visits() -> u32 {
+    DEFAULT_MAX_NODE_VISITS
+}
+
+impl FlowTimeouts {
+    pub fn node_timeout(&self) -> Option<Duration> {
+        self.node_ms.map(Duration::from_millis)
+    }
+
+    /// Absolute deadline for a run starting now.
+    pub fn run_deadline(&self) -> Option<Instant> {
+        self.run_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms))
+    }
+}
+
 impl From<WebhookBindingConfig> for WebhookPolicy {
     fn from(value: WebhookBindingConfig) -> Self {
         Self {
@@ -13,7 +13,11 @@ pub struct FlowSummary {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct FlowSchema {
     pub id: String,
+    /// JSON Schema for the flow input.
     pub schema_json: serde_json::Value,
+    /// JSON Schema for the flow output.
+    #[serde(default)]
+    pub output_schema_json: serde_json::Value,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
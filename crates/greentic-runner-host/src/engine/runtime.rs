@@ -1,8 +1,9 @@
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::{Context, Result, anyhow};
-use greentic_session::SessionData;
+use greentic_session::{SessionData, SessionKey as StoreSessionKey};
 use greentic_types::{
     EnvId, FlowId, GreenticError, SessionCursor as TypesSessionCursor, TenantCtx, TenantId, UserId,
 };
@@ -20,15 +21,33 @@ use super::registry::{Adapter, AdapterCall, AdapterRegistry};
 use super::shims::{InMemorySessionHost, InMemoryStateHost};
 use super::state_machine::{FlowDefinition, FlowStep, PAYLOAD_FROM_LAST_INPUT};
 
-use crate::config::HostConfig;
-use crate::pack::FlowDescriptor;
-use crate::runner::engine::{FlowContext, FlowEngine, FlowSnapshot, FlowStatus, FlowWait};
+use crate::config::{HostConfig, PackChangePolicy, WaitSelection};
+use crate::runner::active_runs::ActiveRuns;
+use crate::runner::engine::{
+    EgressSender, FlowContext, FlowEngine, FlowSnapshot, FlowStatus, FlowWait, NodeBudget,
+    unix_millis,
+};
+use crate::runner::errors::{ErrorKind, classify};
+use crate::runner::journal::Journal;
 use crate::runner::mocks::MockLayer;
+use crate::runner::pack_pins::PinnedEngines;
+use crate::runner::resume_tokens::{ResumeTokenStore, new_resume_token};
+use crate::runner::wait_timeouts::{PendingWakeup, WaitTimeoutStore};
 use crate::storage::session::DynSessionStore;
 
-const DEFAULT_ENV: &str = "local";
+pub(crate) const DEFAULT_ENV: &str = "local";
 const PACK_FLOW_ADAPTER: &str = "pack_flow";
+/// How long a pin outlives the wake-up of its wait, so the poller still finds the engine.
+const PIN_GRACE_MS: u64 = 60_000;
+
+tokio::task_local! {
+    /// Egress subscriber of the activity being handled; carried across the state machine to
+    /// the pack flow adapter.
+    static EGRESS: EgressSender;
+}
 
+/// Snapshots of waiting flows, one per session and flow, so a session can wait in several
+/// flows at once.
 #[derive(Clone)]
 pub struct FlowResumeStore {
     store: DynSessionStore,
@@ -39,37 +58,52 @@ impl FlowResumeStore {
         Self { store }
     }
 
-    fn fetch(&self, envelope: &IngressEnvelope) -> GResult<Option<FlowSnapshot>> {
-        let (mut ctx, user, _) = build_store_ctx(envelope)?;
-        ctx = ctx.with_user(Some(user.clone()));
-        if let Some((_key, data)) = self
-            .store
-            .find_by_user(&ctx, &user)
-            .map_err(map_store_error)?
-        {
+    fn fetch(&self, envelope: &IngressEnvelope) -> GResult<Option<FlowResumeRecord>> {
+        Ok(self.lookup(envelope)?.map(|(_key, record)| record))
+    }
+
+    /// Find the stored wait of the envelope's flow. Falls back to the single per-session
+    /// record older hosts kept, which is moved to the flow's own key on the next save.
+    fn lookup(
+        &self,
+        envelope: &IngressEnvelope,
+    ) -> GResult<Option<(StoreSessionKey, FlowResumeRecord)>> {
+        let (ctx, user, hint) = build_store_ctx(envelope)?;
+        for user in [user, derive_user_id(&hint)?] {
+            let ctx = ctx.clone().with_user(Some(user.clone()));
+            let Some((key, data)) = self
+                .store
+                .find_by_user(&ctx, &user)
+                .map_err(map_store_error)?
+            else {
+                continue;
+            };
             let record: FlowResumeRecord =
                 serde_json::from_str(&data.context_json).map_err(|err| RunnerError::Session {
                     reason: format!("failed to decode flow resume snapshot: {err}"),
                 })?;
             if record.snapshot.flow_id == envelope.flow_id {
-                return Ok(Some(record.snapshot));
+                return Ok(Some((key, record)));
             }
         }
         Ok(None)
     }
 
-    fn save(&self, envelope: &IngressEnvelope, wait: &FlowWait) -> GResult<()> {
+    fn save(
+        &self,
+        envelope: &IngressEnvelope,
+        wait: &FlowWait,
+        pack_digest: Option<&str>,
+    ) -> GResult<()> {
         let (ctx, user, hint) = build_store_ctx(envelope)?;
         let record = FlowResumeRecord {
             snapshot: wait.snapshot.clone(),
             reason: wait.reason.clone(),
+            pack_digest: pack_digest.map(str::to_string),
+            saved_at_ms: unix_millis(),
         };
         let data = record_to_session_data(&record, ctx.clone(), &user, &hint)?;
-        let existing = self
-            .store
-            .find_by_user(&ctx, &user)
-            .map_err(map_store_error)?;
-        if let Some((key, _)) = existing {
+        if let Some((key, _)) = self.lookup(envelope)? {
             self.store
                 .update_session(&key, data)
                 .map_err(map_store_error)?;
@@ -82,12 +116,7 @@ impl FlowResumeStore {
     }
 
     fn clear(&self, envelope: &IngressEnvelope) -> GResult<()> {
-        let (ctx, user, _) = build_store_ctx(envelope)?;
-        if let Some((key, _)) = self
-            .store
-            .find_by_user(&ctx, &user)
-            .map_err(map_store_error)?
-        {
+        if let Some((key, _)) = self.lookup(envelope)? {
             self.store.remove_session(&key).map_err(map_store_error)?;
         }
         Ok(())
@@ -99,14 +128,18 @@ struct FlowResumeRecord {
     snapshot: FlowSnapshot,
     #[serde(default)]
     reason: Option<String>,
+    /// Digest of the pack that saved the snapshot, when the pack was loaded with one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pack_digest: Option<String>,
+    /// Unix time in milliseconds; orders a session's waits for [`WaitSelection::Latest`].
+    #[serde(default)]
+    saved_at_ms: u64,
 }
 
+/// Store context, the user id a flow's wait is filed under and the session hint.
 fn build_store_ctx(envelope: &IngressEnvelope) -> GResult<(TenantCtx, UserId, String)> {
-    let hint = envelope
-        .session_hint
-        .clone()
-        .unwrap_or_else(|| envelope.canonical_session_hint());
-    let user = derive_user_id(&hint)?;
+    let hint = envelope.session_key();
+    let user = derive_user_id(&envelope.wait_key())?;
     let mut ctx = envelope.tenant_ctx();
     ctx = ctx.with_session(hint.clone());
     Ok((ctx, user, hint))
@@ -189,6 +222,8 @@ mod tests {
                 flow_id: "flow.main".into(),
                 next_node: "node-2".into(),
                 state,
+                call_stack: Vec::new(),
+                timeout: None,
             },
         }
     }
@@ -209,10 +244,11 @@ mod tests {
         assert!(store.fetch(&envelope)?.is_none());
 
         let wait = sample_wait();
-        store.save(&envelope, &wait)?;
-        let snapshot = store.fetch(&envelope)?.expect("snapshot missing");
-        assert_eq!(snapshot.flow_id, wait.snapshot.flow_id);
-        assert_eq!(snapshot.next_node, wait.snapshot.next_node);
+        store.save(&envelope, &wait, Some("sha256:abc"))?;
+        let record = store.fetch(&envelope)?.expect("snapshot missing");
+        assert_eq!(record.snapshot.flow_id, wait.snapshot.flow_id);
+        assert_eq!(record.snapshot.next_node, wait.snapshot.next_node);
+        assert_eq!(record.pack_digest.as_deref(), Some("sha256:abc"));
 
         store.clear(&envelope)?;
         assert!(store.fetch(&envelope)?.is_none());
@@ -224,18 +260,297 @@ mod tests {
         let store = FlowResumeStore::new(new_session_store());
         let envelope = sample_envelope();
         let mut wait = sample_wait();
-        store.save(&envelope, &wait)?;
+        store.save(&envelope, &wait, None)?;
 
         wait.snapshot.next_node = "node-3".into();
         wait.reason = Some("retry".into());
-        store.save(&envelope, &wait)?;
+        store.save(&envelope, &wait, None)?;
 
-        let snapshot = store.fetch(&envelope)?.expect("snapshot missing");
-        assert_eq!(snapshot.next_node, "node-3");
+        let record = store.fetch(&envelope)?.expect("snapshot missing");
+        assert_eq!(record.snapshot.next_node, "node-3");
         store.clear(&envelope)?;
         Ok(())
     }
 
+    #[test]
+    fn resume_store_keeps_one_wait_per_flow() -> GResult<()> {
+        let store = FlowResumeStore::new(new_session_store());
+        let main = sample_envelope();
+        let other = IngressEnvelope {
+            flow_id: "flow.other".into(),
+            ..sample_envelope()
+        };
+        let wait = sample_wait();
+        store.save(&main, &wait, None)?;
+        assert!(store.fetch(&other)?.is_none());
+
+        let mut other_wait = sample_wait();
+        other_wait.snapshot.flow_id = "flow.other".into();
+        other_wait.snapshot.next_node = "node-9".into();
+        store.save(&other, &other_wait, None)?;
+        assert_eq!(
+            store.fetch(&main)?.expect("main wait").snapshot.next_node,
+            "node-2"
+        );
+        assert_eq!(
+            store.fetch(&other)?.expect("other wait").snapshot.next_node,
+            "node-9"
+        );
+
+        store.clear(&main)?;
+        assert!(store.fetch(&main)?.is_none());
+        assert!(store.fetch(&other)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn per_session_records_move_to_their_flow_key() -> GResult<()> {
+        let sessions = new_session_store();
+        let store = FlowResumeStore::new(Arc::clone(&sessions));
+        let envelope = sample_envelope();
+        let hint = envelope.session_key();
+        let legacy_user = derive_user_id(&hint)?;
+        let record = FlowResumeRecord {
+            snapshot: sample_wait().snapshot,
+            reason: None,
+            pack_digest: None,
+            saved_at_ms: 0,
+        };
+        let ctx = envelope.tenant_ctx().with_session(hint.clone());
+        let data = record_to_session_data(&record, ctx.clone(), &legacy_user, &hint)?;
+        sessions
+            .create_session(&ctx, data)
+            .map_err(map_store_error)?;
+
+        let found = store.fetch(&envelope)?.expect("legacy wait");
+        assert_eq!(found.snapshot.next_node, "node-2");
+
+        store.save(&envelope, &sample_wait(), None)?;
+        let legacy_ctx = ctx.with_user(Some(legacy_user.clone()));
+        assert!(
+            sessions
+                .find_by_user(&legacy_ctx, &legacy_user)
+                .map_err(map_store_error)?
+                .is_none()
+        );
+        assert!(store.fetch(&envelope)?.expect("moved wait").saved_at_ms > 0);
+        Ok(())
+    }
+
+    fn example_config() -> HostConfig {
+        HostConfig::load_from_path(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../examples/bindings/default.bindings.yaml"),
+        )
+        .unwrap()
+    }
+
+    /// A runtime serving `flow.main`, which waits for approval and then echoes it, and
+    /// `flow.review`, which waits twice before echoing.
+    async fn approval_runtime(
+        config: Arc<HostConfig>,
+        previous: Option<&StateMachineRuntime>,
+    ) -> StateMachineRuntime {
+        use crate::engine::shims::{InMemorySessionHost, InMemoryStateHost};
+        use crate::storage::state::new_state_store;
+        use greentic_flow::ir::{FlowIR, NodeIR, RouteIR};
+
+        let mut engine = FlowEngine::new(Vec::new(), Arc::clone(&config))
+            .await
+            .unwrap();
+        let node = |component: &str, payload: Value, to: Option<&str>| NodeIR {
+            component: component.into(),
+            payload_expr: payload,
+            routes: to
+                .map(|to| RouteIR {
+                    to: Some(to.into()),
+                    out: false,
+                })
+                .into_iter()
+                .collect(),
+        };
+        engine.insert_test_flow(FlowIR {
+            id: "flow.main".into(),
+            flow_type: "messaging".into(),
+            start: Some("ask".into()),
+            parameters: json!({
+                "input_schema": { "type": "object", "required": ["text"] }
+            }),
+            nodes: [
+                (
+                    "ask".to_string(),
+                    node(
+                        "session.wait",
+                        json!({ "reason": "approval" }),
+                        Some("done"),
+                    ),
+                ),
+                (
+                    "done".to_string(),
+                    node(
+                        "qa.process",
+                        json!({ "approved": "{{input.approved}}" }),
+                        None,
+                    ),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        });
+        engine.insert_test_flow(FlowIR {
+            id: "flow.review".into(),
+            flow_type: "messaging".into(),
+            start: Some("first".into()),
+            parameters: json!({}),
+            nodes: [
+                (
+                    "first".to_string(),
+                    node("session.wait", json!({ "reason": "first" }), Some("second")),
+                ),
+                (
+                    "second".to_string(),
+                    node("session.wait", json!({ "reason": "second" }), Some("done")),
+                ),
+                (
+                    "done".to_string(),
+                    node(
+                        "qa.process",
+                        json!({ "approved": "{{input.approved}}" }),
+                        None,
+                    ),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        });
+        let state_store = new_state_store();
+        StateMachineRuntime::from_flow_engine(
+            Arc::clone(&config),
+            Arc::new(engine.with_state_store(Arc::clone(&state_store))),
+            None,
+            previous
+                .map(StateMachineRuntime::pinned_engines)
+                .unwrap_or_default(),
+            previous
+                .map(StateMachineRuntime::active_runs)
+                .unwrap_or_default(),
+            Arc::new(InMemorySessionHost::new()),
+            new_session_store(),
+            Arc::new(InMemoryStateHost::new()),
+            WaitTimeoutStore::new(Arc::clone(&state_store), &config.tenant).unwrap(),
+            ResumeTokenStore::new(state_store, &config.tenant).unwrap(),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn token_resumes_skip_the_ingress_schema() {
+        let config = Arc::new(example_config());
+        let runtime = approval_runtime(Arc::clone(&config), None).await;
+
+        let mut envelope = sample_envelope();
+        envelope.tenant = config.tenant.clone();
+        let pending = runtime.handle(envelope.clone()).await.unwrap();
+        assert_eq!(pending["status"], json!("pending"));
+        let token = pending["resume_token"].as_str().unwrap().to_string();
+
+        // The activity path still enforces the schema.
+        envelope.payload = json!({ "approved": true });
+        let err = runtime.handle(envelope).await.unwrap_err();
+        assert!(format!("{err:#}").contains("does not match its schema"));
+
+        let resumed = runtime
+            .resume_with_token(&token, json!({ "approved": true }))
+            .await
+            .unwrap()
+            .expect("session waiting");
+        assert_eq!(resumed, json!({ "approved": "true" }));
+    }
+
+    #[tokio::test]
+    async fn each_wait_gets_a_token_that_resumes_it_once() {
+        let config = Arc::new(example_config());
+        let runtime = approval_runtime(Arc::clone(&config), None).await;
+        let tokens = &runtime.pack_flows.as_ref().unwrap().resume_tokens;
+
+        let mut envelope = sample_envelope();
+        envelope.tenant = config.tenant.clone();
+        envelope.flow_id = "flow.review".into();
+        let pending = runtime.handle(envelope.clone()).await.unwrap();
+        let first = pending["resume_token"].as_str().unwrap().to_string();
+
+        // Two concurrent resumes with the same token: only one gets through.
+        let (a, b) = tokio::join!(
+            runtime.resume_with_token(&first, json!({})),
+            runtime.resume_with_token(&first, json!({})),
+        );
+        let resumed = [a.unwrap(), b.unwrap()];
+        assert_eq!(resumed.iter().flatten().count(), 1, "{resumed:?}");
+
+        // The second wait has its own token; the first one is spent.
+        let second = tokens.token_for(&envelope.wait_key()).unwrap().unwrap();
+        assert_ne!(second, first);
+        assert!(
+            runtime
+                .resume_with_token(&first, json!({}))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let done = runtime
+            .resume_with_token(&second, json!({ "approved": true }))
+            .await
+            .unwrap();
+        assert_eq!(done, Some(json!({ "approved": "true" })));
+        assert!(tokens.resolve(&second).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn runs_stay_cancellable_across_a_reload() {
+        let config = Arc::new(example_config());
+        let old = approval_runtime(Arc::clone(&config), None).await;
+        let in_flight = old.pack_flows.as_ref().unwrap().active_runs.start("demo:a");
+
+        let new = approval_runtime(Arc::clone(&config), Some(&old)).await;
+        drop(old);
+        assert_eq!(new.cancel_runs(Some("demo:a")), 1);
+        assert!(in_flight.token().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn runs_are_journaled_when_the_bindings_enable_it() {
+        let dir = std::env::temp_dir().join(format!("greentic-journals-{}", std::process::id()));
+        let mut config = example_config();
+        assert_eq!(config.journal_dir(), None);
+        config.journal = crate::config::JournalConfig {
+            record: true,
+            dir: Some(dir.clone()),
+        };
+        let config = Arc::new(config);
+        let runtime = approval_runtime(Arc::clone(&config), None).await;
+
+        let mut envelope = sample_envelope();
+        envelope.tenant = config.tenant.clone();
+        runtime.handle(envelope).await.unwrap();
+
+        let journals = std::fs::read_dir(dir.join(&config.tenant))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(journals.len(), 1);
+        let name = journals[0].file_name().unwrap().to_string_lossy();
+        assert!(name.starts_with("flow.main-"), "{name}");
+        let journal = Journal::load(&journals[0]).unwrap();
+        let nodes = journal
+            .entries()
+            .into_iter()
+            .map(|entry| entry.node_id)
+            .collect::<Vec<_>>();
+        assert_eq!(nodes, vec!["ask".to_string()]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     #[test]
     fn canonicalize_populates_defaults() {
         let envelope = IngressEnvelope {
@@ -266,6 +581,7 @@ mod tests {
 
 pub struct StateMachineRuntime {
     runner: Runner,
+    pack_flows: Option<PackFlowAdapter>,
 }
 
 impl StateMachineRuntime {
@@ -292,16 +608,27 @@ impl StateMachineRuntime {
             builder = builder.with_flow(flow);
         }
         let runner = builder.build()?;
-        Ok(Self { runner })
+        Ok(Self {
+            runner,
+            pack_flows: None,
+        })
     }
 
     /// Build a state-machine runtime that proxies pack flows through the legacy FlowEngine.
+    /// `digest` identifies the pack behind `engine`; `pins` is shared with the runtimes this
+    /// one replaces, so sessions waiting on them can still resume there.
+    #[allow(clippy::too_many_arguments)]
     pub fn from_flow_engine(
         config: Arc<HostConfig>,
         engine: Arc<FlowEngine>,
+        digest: Option<String>,
+        pins: PinnedEngines,
+        active_runs: ActiveRuns,
         session_host: Arc<dyn SessionHost>,
         session_store: DynSessionStore,
         state_host: Arc<dyn StateHost>,
+        wait_timeouts: WaitTimeoutStore,
+        resume_tokens: ResumeTokenStore,
         mocks: Option<Arc<MockLayer>>,
     ) -> Result<Self> {
         let secrets_cfg = Arc::clone(&config);
@@ -322,18 +649,21 @@ impl StateMachineRuntime {
         let host = HostBundle::new(secrets, telemetry, session_host, state_host);
         let resume_store = FlowResumeStore::new(session_store);
 
-        let mut adapters = AdapterRegistry::default();
-        adapters.register(
-            PACK_FLOW_ADAPTER,
-            Box::new(PackFlowAdapter::new(
-                Arc::clone(&config),
-                Arc::clone(&engine),
-                resume_store,
-                mocks,
-            )),
+        let pack_flows = PackFlowAdapter::new(
+            Arc::clone(&config),
+            Arc::clone(&engine),
+            digest,
+            pins,
+            active_runs,
+            resume_store,
+            wait_timeouts,
+            resume_tokens,
+            mocks,
         );
+        let mut adapters = AdapterRegistry::default();
+        adapters.register(PACK_FLOW_ADAPTER, Box::new(pack_flows.clone()));
 
-        let flows = build_flow_definitions(engine.flows());
+        let flows = build_flow_definitions(&engine);
         let mut builder = RunnerBuilder::new()
             .with_host(host)
             .with_adapters(adapters)
@@ -344,16 +674,20 @@ impl StateMachineRuntime {
         let runner = builder
             .build()
             .map_err(|err| anyhow!("state machine init failed: {err}"))?;
-        Ok(Self { runner })
+        Ok(Self {
+            runner,
+            pack_flows: Some(pack_flows),
+        })
     }
 
-    /// Execute the flow associated with the provided ingress event.
-    pub async fn handle(&self, envelope: IngressEnvelope) -> Result<Value> {
+    /// Execute the flow associated with the provided ingress event, or the session's waiting
+    /// flow picked by the bindings' `resume.select` rule.
+    pub async fn handle(&self, mut envelope: IngressEnvelope) -> Result<Value> {
+        if let Some(pack_flows) = &self.pack_flows {
+            pack_flows.select_flow(&mut envelope)?;
+        }
         let tenant_ctx = envelope.tenant_ctx();
-        let session_hint = envelope
-            .session_hint
-            .clone()
-            .unwrap_or_else(|| envelope.canonical_session_hint());
+        let session_hint = envelope.session_key();
         let input =
             serde_json::to_value(&envelope).context("failed to serialise ingress envelope")?;
         let request = RunFlowRequest {
@@ -370,12 +704,123 @@ impl StateMachineRuntime {
         let outcome = result.outcome;
         Ok(outcome.get("response").cloned().unwrap_or(outcome))
     }
+
+    /// Like [`Self::handle`], but `emit*` payloads go to `egress` as they run. The returned
+    /// response only holds what was not streamed.
+    pub async fn handle_streaming(
+        &self,
+        envelope: IngressEnvelope,
+        egress: EgressSender,
+    ) -> Result<Value> {
+        EGRESS.scope(egress, self.handle(envelope)).await
+    }
+
+    /// Resume the flow behind an expired `session.wait` through its timeout route. Returns
+    /// `None` when the session no longer waits on that timeout (it was answered or re-armed)
+    /// or its snapshot was discarded after a pack change.
+    pub async fn resume_expired(&self, wakeup: &PendingWakeup) -> Result<Option<Value>> {
+        let pack_flows = self
+            .pack_flows
+            .as_ref()
+            .context("runtime has no pack flows to resume")?;
+        let Some(record) = pack_flows.resume.fetch(&wakeup.envelope)? else {
+            return Ok(None);
+        };
+        if record.snapshot.timeout().map(|timeout| timeout.wake_at_ms) != Some(wakeup.wake_at_ms) {
+            return Ok(None);
+        }
+        let placement = pack_flows.place(&wakeup.envelope, record).await?;
+        if placement.snapshot.is_none() {
+            return Ok(None);
+        }
+        let outcome = pack_flows
+            .run(
+                &wakeup.envelope.flow_id,
+                &wakeup.envelope,
+                FlowStart::Expired(Box::new(placement)),
+            )
+            .await
+            .map_err(|err| anyhow!("flow execution failed: {err}"))?;
+        Ok(Some(outcome.get("response").cloned().unwrap_or(outcome)))
+    }
+
+    /// Like [`Self::resume_expired`], but `emit*` payloads go to `egress` as they run.
+    pub async fn resume_expired_streaming(
+        &self,
+        wakeup: &PendingWakeup,
+        egress: EgressSender,
+    ) -> Result<Option<Value>> {
+        EGRESS.scope(egress, self.resume_expired(wakeup)).await
+    }
+
+    /// Resume the session behind `token` with `input` as the new activity payload. Returns
+    /// `None` when the token is unknown or its session is no longer waiting. `input` answers
+    /// the `session.wait` node, so it is not checked against the flow's input schema, which
+    /// describes inbound activities.
+    pub async fn resume_with_token(&self, token: &str, input: Value) -> Result<Option<Value>> {
+        let pack_flows = self
+            .pack_flows
+            .as_ref()
+            .context("runtime has no pack flows to resume")?;
+        let Some(stored) = pack_flows.resume_tokens.claim(token)? else {
+            return Ok(None);
+        };
+        if pack_flows.resume.fetch(&stored)?.is_none() {
+            return Ok(None);
+        }
+        let envelope = IngressEnvelope {
+            action: Some("resume".into()),
+            activity_id: None,
+            timestamp: None,
+            payload: input,
+            ..stored.clone()
+        };
+        let outcome = match pack_flows
+            .run(&envelope.flow_id, &envelope, FlowStart::Activity)
+            .await
+        {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                // The flow still waits on the same snapshot; let the caller retry.
+                pack_flows.resume_tokens.register(token, &stored)?;
+                return Err(anyhow!("flow execution failed: {err}"));
+            }
+        };
+        Ok(Some(outcome.get("response").cloned().unwrap_or(outcome)))
+    }
+
+    /// Cancel the pack flow runs in progress for `session`, or all of them when `session` is
+    /// `None`. Returns how many runs were signalled; their stored sessions are left as they
+    /// were before the cancelled activity.
+    pub fn cancel_runs(&self, session: Option<&str>) -> usize {
+        self.pack_flows
+            .as_ref()
+            .map_or(0, |pack_flows| pack_flows.active_runs.cancel(session))
+    }
+
+    /// Engines kept alive for waiting sessions; pass to the runtime that replaces this one.
+    pub fn pinned_engines(&self) -> PinnedEngines {
+        self.pack_flows
+            .as_ref()
+            .map(|pack_flows| pack_flows.pins.clone())
+            .unwrap_or_default()
+    }
+
+    /// Runs in progress; pass to the runtime that replaces this one so they stay cancellable.
+    pub fn active_runs(&self) -> ActiveRuns {
+        self.pack_flows
+            .as_ref()
+            .map(|pack_flows| pack_flows.active_runs.clone())
+            .unwrap_or_default()
+    }
 }
 
-fn build_flow_definitions(flows: &[FlowDescriptor]) -> Vec<FlowDefinition> {
-    flows
+fn build_flow_definitions(engine: &FlowEngine) -> Vec<FlowDefinition> {
+    engine
+        .flows()
         .iter()
         .map(|descriptor| {
+            let schemas = engine.flow_schemas(&descriptor.id).unwrap_or_default();
             FlowDefinition::new(
                 super::api::FlowSummary {
                     id: descriptor.id.clone(),
@@ -386,102 +831,313 @@ fn build_flow_definitions(flows: &[FlowDescriptor]) -> Vec<FlowDefinition> {
                     version: descriptor.version.clone(),
                     description: descriptor.description.clone(),
                 },
-                serde_json::json!({
-                    "type": "object"
-                }),
+                schemas.input.unwrap_or_else(|| json!({})),
                 vec![FlowStep::Adapter(AdapterCall {
                     adapter: PACK_FLOW_ADAPTER.into(),
                     operation: descriptor.id.clone(),
                     payload: Value::String(PAYLOAD_FROM_LAST_INPUT.into()),
                 })],
             )
+            .with_output_schema(schemas.output.unwrap_or_else(|| json!({})))
+            // Pack flows are fed an ingress envelope; the schema covers its payload.
+            .with_input_pointer("/payload")
         })
         .collect()
 }
 
+#[derive(Clone)]
 struct PackFlowAdapter {
     tenant: String,
     config: Arc<HostConfig>,
     engine: Arc<FlowEngine>,
+    digest: Option<String>,
+    pins: PinnedEngines,
     resume: FlowResumeStore,
+    wait_timeouts: WaitTimeoutStore,
+    resume_tokens: ResumeTokenStore,
     mocks: Option<Arc<MockLayer>>,
+    active_runs: ActiveRuns,
+}
+
+/// How a pack flow run begins.
+enum FlowStart {
+    /// Resume a stored snapshot if the session has one, otherwise start fresh.
+    Activity,
+    /// Follow the timeout route of the snapshot's expired `session.wait`.
+    Expired(Box<Placement>),
+}
+
+/// The engine a run executes on, and the snapshot it resumes (if any).
+struct Placement {
+    engine: Arc<FlowEngine>,
+    digest: Option<String>,
+    snapshot: Option<FlowSnapshot>,
 }
 
 impl PackFlowAdapter {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         config: Arc<HostConfig>,
         engine: Arc<FlowEngine>,
+        digest: Option<String>,
+        pins: PinnedEngines,
+        active_runs: ActiveRuns,
         resume: FlowResumeStore,
+        wait_timeouts: WaitTimeoutStore,
+        resume_tokens: ResumeTokenStore,
         mocks: Option<Arc<MockLayer>>,
     ) -> Self {
         Self {
             tenant: config.tenant.clone(),
             config,
             engine,
+            digest,
+            pins,
             resume,
+            wait_timeouts,
+            resume_tokens,
             mocks,
+            active_runs,
         }
     }
-}
 
-#[async_trait::async_trait]
-impl Adapter for PackFlowAdapter {
-    async fn call(&self, call: &AdapterCall) -> GResult<Value> {
-        let envelope: IngressEnvelope =
-            serde_json::from_value(call.payload.clone()).map_err(|err| {
-                RunnerError::AdapterCall {
-                    reason: format!("invalid ingress payload: {err}"),
-                }
-            })?;
-        let flow_id = call.operation.clone();
+    /// Write a recorded run to `dir/<tenant>/<flow>-<millis>-<session hash>.json`.
+    fn save_journal(&self, dir: &Path, flow_id: &str, session: &str, journal: &Journal) {
+        let digest = Sha256::digest(session.as_bytes());
+        let dir = dir.join(&self.tenant);
+        let path = dir.join(format!(
+            "{flow_id}-{}-{}.json",
+            unix_millis(),
+            hex::encode(&digest[..8])
+        ));
+        let saved = std::fs::create_dir_all(&dir)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| journal.save(&path));
+        if let Err(err) = saved {
+            tracing::warn!(path = %path.display(), error = %err, "failed to save run journal");
+        }
+    }
+
+    async fn run(
+        &self,
+        flow_id: &str,
+        envelope: &IngressEnvelope,
+        start: FlowStart,
+    ) -> GResult<Value> {
         let action_owned = envelope.action.clone();
-        let session_owned = envelope
-            .session_hint
-            .clone()
-            .unwrap_or_else(|| envelope.canonical_session_hint());
+        let session_owned = envelope.session_key();
+        let wait_key = envelope.wait_key();
+        let user_owned = envelope.user.clone();
+        let resume_token = new_resume_token();
         let provider_owned = envelope.provider.clone();
         let payload = envelope.payload.clone();
         let retry_config = self.config.mcp_retry_config().into();
 
         let mocks = self.mocks.as_deref();
+        let egress = EGRESS.try_with(EgressSender::clone).ok();
+        let active_run = self.active_runs.start(&session_owned);
+        let journal = self
+            .config
+            .journal_dir()
+            .map(|dir| (dir, Journal::recording()));
         let ctx = FlowContext {
             tenant: &self.tenant,
-            flow_id: &flow_id,
+            flow_id,
             node_id: None,
             tool: None,
             action: action_owned.as_deref(),
             session_id: Some(session_owned.as_str()),
+            user_id: user_owned.as_deref(),
             provider_id: provider_owned.as_deref(),
             retry_config,
             observer: None,
             mocks,
+            deadline: self.config.timeouts.run_deadline(),
+            node_timeout: self.config.timeouts.node_timeout(),
+            node_budget: NodeBudget::new(self.config.limits.max_node_visits),
+            resume_token: Some(resume_token.as_str()),
+            egress: egress.as_ref(),
+            cancel: active_run.token().clone(),
+            journal: journal.as_ref().map(|(_, journal)| journal),
+            debugger: None,
         };
 
-        let execution = if let Some(snapshot) = self.resume.fetch(&envelope)? {
-            self.engine.resume(ctx, snapshot, payload).await
-        } else {
-            self.engine.execute(ctx, payload).await
+        let (placement, expired) = match start {
+            FlowStart::Expired(placement) => (*placement, true),
+            FlowStart::Activity => match self.resume.fetch(envelope)? {
+                Some(record) => (self.place(envelope, record).await?, false),
+                None => (self.current(None), false),
+            },
+        };
+        let Placement {
+            engine,
+            digest,
+            snapshot,
+        } = placement;
+        let execution = match snapshot {
+            Some(snapshot) if expired => engine.resume_expired(ctx, snapshot).await,
+            Some(snapshot) => engine.resume(ctx, snapshot, payload).await,
+            None => engine.execute(ctx, payload).await,
+        };
+        if let Some((dir, journal)) = &journal {
+            self.save_journal(dir, flow_id, &session_owned, journal);
         }
-        .map_err(|err| RunnerError::AdapterCall {
-            reason: err.to_string(),
+        let execution = execution.map_err(|err| match classify(&err) {
+            ErrorKind::Policy => RunnerError::Policy {
+                reason: err.to_string(),
+            },
+            _ => RunnerError::AdapterCall {
+                reason: err.to_string(),
+            },
         })?;
 
         match execution.status {
             FlowStatus::Completed => {
-                self.resume.clear(&envelope)?;
+                self.discard(envelope)?;
                 Ok(execution.output)
             }
             FlowStatus::Waiting(wait) => {
-                self.resume.save(&envelope, &wait)?;
+                self.resume.save(envelope, &wait, digest.as_deref())?;
+                match &digest {
+                    Some(digest) => {
+                        let expires_at_ms = match wait.snapshot.timeout() {
+                            Some(timeout) => timeout.wake_at_ms.saturating_add(PIN_GRACE_MS),
+                            None => unix_millis().saturating_add(self.config.resume.pin_ttl_ms),
+                        };
+                        self.pins.pin(&wait_key, digest, &engine, expires_at_ms)
+                    }
+                    None => self.pins.release(&wait_key),
+                }
+                match wait.snapshot.timeout() {
+                    Some(timeout) => self.wait_timeouts.arm(envelope, timeout.wake_at_ms)?,
+                    None => self.wait_timeouts.disarm(&wait_key, None)?,
+                }
+                self.resume_tokens.register(&resume_token, envelope)?;
                 Ok(json!({
                     "status": "pending",
                     "reason": wait.reason,
+                    "resume_token": resume_token,
                     "resume": wait.snapshot,
                     "response": execution.output,
                 }))
             }
         }
     }
+
+    fn current(&self, snapshot: Option<FlowSnapshot>) -> Placement {
+        Placement {
+            engine: Arc::clone(&self.engine),
+            digest: self.digest.clone(),
+            snapshot,
+        }
+    }
+
+    /// Pick the engine a stored snapshot resumes on: the current one, or the engine of the
+    /// pack that saved it while sessions are pinned there. Snapshots from a pack that is gone
+    /// follow the bindings' resume policy; a discarded snapshot places a fresh start.
+    async fn place(
+        &self,
+        envelope: &IngressEnvelope,
+        record: FlowResumeRecord,
+    ) -> GResult<Placement> {
+        let FlowResumeRecord {
+            mut snapshot,
+            pack_digest,
+            ..
+        } = record;
+        let saved = match (pack_digest, self.digest.as_deref()) {
+            (Some(saved), Some(current)) if saved != current => saved,
+            _ => return Ok(self.current(Some(snapshot))),
+        };
+        if let Some(engine) = self.pins.engine(&saved) {
+            return Ok(Placement {
+                engine,
+                digest: Some(saved),
+                snapshot: Some(snapshot),
+            });
+        }
+        let policy = &self.config.resume;
+        if policy.on_pack_change == PackChangePolicy::Migrate {
+            snapshot.migrate_nodes(&policy.migrations);
+            if self.engine.can_resume(&snapshot).await {
+                return Ok(self.current(Some(snapshot)));
+            }
+        }
+        tracing::warn!(
+            tenant = %self.tenant,
+            flow_id = %snapshot.flow_id,
+            next_node = %snapshot.next_node,
+            saved_digest = %saved,
+            policy = ?policy.on_pack_change,
+            "discarding snapshot saved by a replaced pack"
+        );
+        self.discard(envelope)?;
+        if policy.on_pack_change == PackChangePolicy::Fail {
+            return Err(RunnerError::AdapterCall {
+                reason: format!(
+                    "session snapshot for flow {} was saved by pack {saved}, which is no longer loaded",
+                    snapshot.flow_id
+                ),
+            });
+        }
+        Ok(self.current(None))
+    }
+
+    /// Forget everything stored for the envelope's waiting flow: snapshot, wake-up, resume
+    /// token and pinned engine.
+    fn discard(&self, envelope: &IngressEnvelope) -> GResult<()> {
+        let wait_key = envelope.wait_key();
+        self.resume.clear(envelope)?;
+        self.wait_timeouts.disarm(&wait_key, None)?;
+        self.resume_tokens.revoke(&wait_key)?;
+        self.pins.release(&wait_key);
+        Ok(())
+    }
+
+    /// Point `envelope` at the flow it should run. The targeted flow wins when it is waiting
+    /// or under [`WaitSelection::Flow`]; with [`WaitSelection::Latest`] the session's most
+    /// recently saved wait in another flow is resumed instead.
+    fn select_flow(&self, envelope: &mut IngressEnvelope) -> GResult<()> {
+        if self.config.resume.select == WaitSelection::Flow
+            || self.resume.fetch(envelope)?.is_some()
+        {
+            return Ok(());
+        }
+        let mut latest: Option<(u64, &str, &str)> = None;
+        for descriptor in self.engine.flows() {
+            if descriptor.id == envelope.flow_id {
+                continue;
+            }
+            let candidate = IngressEnvelope {
+                flow_id: descriptor.id.clone(),
+                ..envelope.clone()
+            };
+            if let Some(record) = self.resume.fetch(&candidate)?
+                && latest.is_none_or(|(saved_at_ms, ..)| record.saved_at_ms > saved_at_ms)
+            {
+                latest = Some((record.saved_at_ms, &descriptor.id, &descriptor.flow_type));
+            }
+        }
+        if let Some((_, flow_id, flow_type)) = latest {
+            envelope.flow_id = flow_id.to_string();
+            envelope.flow_type = Some(flow_type.to_string());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Adapter for PackFlowAdapter {
+    async fn call(&self, call: &AdapterCall) -> GResult<Value> {
+        let envelope: IngressEnvelope =
+            serde_json::from_value(call.payload.clone()).map_err(|err| {
+                RunnerError::AdapterCall {
+                    reason: format!("invalid ingress payload: {err}"),
+                }
+            })?;
+        self.run(&call.operation, &envelope, FlowStart::Activity)
+            .await
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -540,6 +1196,19 @@ impl IngressEnvelope {
         self
     }
 
+    /// Session hint the flow runs under.
+    pub fn session_key(&self) -> String {
+        self.session_hint
+            .clone()
+            .unwrap_or_else(|| self.canonical_session_hint())
+    }
+
+    /// Key the flow's snapshot, wake-up and resume token are filed under: one per session
+    /// and flow, so a session can wait in several flows at once.
+    pub fn wait_key(&self) -> String {
+        format!("{}#{}", self.session_key(), self.flow_id)
+    }
+
     pub fn canonical_session_hint(&self) -> String {
         format!(
             "{}:{}:{}:{}:{}",
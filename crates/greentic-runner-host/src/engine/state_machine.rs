@@ -22,7 +22,15 @@ pub const PAYLOAD_FROM_LAST_INPUT: &str = "$ingress";
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FlowDefinition {
     pub summary: FlowSummary,
+    /// JSON Schema the step input must satisfy.
     pub schema: Value,
+    /// JSON Schema describing the flow output; `{}` when undeclared.
+    #[serde(default = "any_schema")]
+    pub output_schema: Value,
+    /// JSON pointer to the part of the step input validated against `schema`; the whole
+    /// input when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_pointer: Option<String>,
     pub steps: Vec<FlowStep>,
 }
 
@@ -31,9 +39,25 @@ impl FlowDefinition {
         Self {
             summary,
             schema,
+            output_schema: any_schema(),
+            input_pointer: None,
             steps,
         }
     }
+
+    pub fn with_output_schema(mut self, schema: Value) -> Self {
+        self.output_schema = schema;
+        self
+    }
+
+    pub fn with_input_pointer(mut self, pointer: impl Into<String>) -> Self {
+        self.input_pointer = Some(pointer.into());
+        self
+    }
+}
+
+fn any_schema() -> Value {
+    json!({})
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -48,6 +72,7 @@ pub struct StateMachine {
     adapters: AdapterRegistry,
     policy: Policy,
     flows: Arc<RwLock<HashMap<String, FlowDefinition>>>,
+    validators: Arc<RwLock<HashMap<String, Arc<jsonschema::Validator>>>>,
 }
 
 impl StateMachine {
@@ -57,12 +82,25 @@ impl StateMachine {
             adapters,
             policy,
             flows: Arc::new(RwLock::new(HashMap::new())),
+            validators: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
     pub fn register_flow(&self, definition: FlowDefinition) {
+        let flow_id = definition.summary.id.clone();
+        match jsonschema::validator_for(&definition.schema) {
+            Ok(validator) => {
+                self.validators
+                    .write()
+                    .insert(flow_id.clone(), Arc::new(validator));
+            }
+            Err(err) => {
+                tracing::warn!(flow_id = %flow_id, error = %err, "invalid input schema; input will not be validated");
+                self.validators.write().remove(&flow_id);
+            }
+        }
         let mut guard = self.flows.write();
-        guard.insert(definition.summary.id.clone(), definition);
+        guard.insert(flow_id, definition);
     }
 
     pub fn list_flows(&self) -> Vec<FlowSummary> {
@@ -77,6 +115,7 @@ impl StateMachine {
             .map(|flow| FlowSchema {
                 id: flow_id.to_string(),
                 schema_json: flow.schema.clone(),
+                output_schema_json: flow.output_schema.clone(),
             })
             .ok_or_else(|| RunnerError::FlowNotFound {
                 flow_id: flow_id.to_string(),
@@ -110,6 +149,7 @@ impl StateMachine {
         };
 
         self.ensure_policy_budget(&flow)?;
+        self.validate_input(&flow, &input)?;
 
         let key = SessionKey::new(tenant, flow_id, session_hint.clone());
         let session_host = &self.host.session;
@@ -139,7 +179,7 @@ impl StateMachine {
             session.waiting = None;
         }
 
-        let mut final_outcome = None;
+        let final_outcome;
         loop {
             if session.cursor.position >= flow.steps.len() {
                 let outcome = session
@@ -160,10 +200,8 @@ impl StateMachine {
 
             match step {
                 FlowStep::Adapter(call) => {
-                    let outcome = self
-                        .execute_adapter_step(&flow, &mut session, call, tenant)
+                    self.execute_adapter_step(&flow, &mut session, call, tenant)
                         .await?;
-                    final_outcome = Some(outcome);
                     continue;
                 }
                 FlowStep::AwaitInput { reason } => {
@@ -230,6 +268,35 @@ impl StateMachine {
         Ok(())
     }
 
+    fn validate_input(&self, flow: &FlowDefinition, input: &Value) -> GResult<()> {
+        let Some(validator) = self.validators.read().get(&flow.summary.id).cloned() else {
+            return Ok(());
+        };
+        let instance = match flow.input_pointer.as_deref() {
+            Some(pointer) => input.pointer(pointer).unwrap_or(&Value::Null),
+            None => input,
+        };
+        let errors = validator
+            .iter_errors(instance)
+            .map(|err| {
+                let path = err.instance_path.to_string();
+                if path.is_empty() {
+                    err.to_string()
+                } else {
+                    format!("{path}: {err}")
+                }
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(policy_violation(format!(
+            "input for flow {} does not match its schema: {}",
+            flow.summary.id,
+            errors.join("; ")
+        )))
+    }
+
     async fn execute_adapter_step(
         &self,
         flow: &FlowDefinition,
@@ -478,6 +545,68 @@ mod tests {
         assert_eq!(history[1]["text"], json!("need help"));
     }
 
+    #[tokio::test]
+    async fn rejects_input_that_violates_the_flow_schema() {
+        let secrets = Arc::new(FnSecretsHost::new(|_| Ok(String::new())));
+        let telemetry = Arc::new(FnTelemetryHost::new(|_, _| Ok(())));
+        let host = Arc::new(HostBundle::new(
+            secrets,
+            telemetry,
+            Arc::new(InMemorySessionHost::new()) as Arc<dyn SessionHost>,
+            Arc::new(InMemoryStateHost::new()) as Arc<dyn StateHost>,
+        ));
+        let mut adapters = AdapterRegistry::default();
+        adapters.register("mock", Box::new(MockChatAdapter::default()));
+        let sm = StateMachine::new(host, adapters, Policy::default());
+
+        let mut flow = test_flow();
+        flow.schema = json!({
+            "type": "object",
+            "required": ["order"],
+            "properties": { "order": { "type": "integer" } }
+        });
+        sm.register_flow(
+            flow.with_output_schema(json!({ "type": "object" }))
+                .with_input_pointer("/payload"),
+        );
+
+        let schema = sm.get_flow_schema("support.flow").unwrap();
+        assert_eq!(schema.schema_json["required"], json!(["order"]));
+        assert_eq!(schema.output_schema_json, json!({ "type": "object" }));
+
+        let tenant_ctx = TenantCtx::new(
+            EnvId::from_str("local").unwrap(),
+            TenantId::from_str("demo").unwrap(),
+        );
+        let err = sm
+            .step(
+                &tenant_ctx,
+                "support.flow",
+                None,
+                json!({ "payload": { "order": "abc" } }),
+            )
+            .await
+            .unwrap_err();
+        match err {
+            RunnerError::Policy { reason } => assert_eq!(
+                reason,
+                "input for flow support.flow does not match its schema: /order: \"abc\" is not of type \"integer\""
+            ),
+            other => panic!("expected policy error, got {other:?}"),
+        }
+
+        let ok = sm
+            .step(
+                &tenant_ctx,
+                "support.flow",
+                None,
+                json!({ "payload": { "order": 42 } }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(ok["status"], json!("pending"));
+    }
+
     fn test_flow() -> FlowDefinition {
         FlowDefinition::new(
             FlowSummary {
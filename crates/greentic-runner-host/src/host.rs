@@ -3,6 +3,7 @@ use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::{Context, Result, anyhow, bail};
+use handlebars::HelperDef;
 use serde_json::Value;
 
 use crate::activity::Activity;
@@ -13,7 +14,8 @@ use crate::engine::runtime::IngressEnvelope;
 use crate::http::health::HealthState;
 use crate::pack::PackRuntime;
 use crate::runner::adapt_timer;
-use crate::runner::engine::FlowEngine;
+use crate::runner::engine::{EngineExtensions, FlowEngine};
+use crate::runner::nodes::{NodeHandler, NodeRegistry};
 use crate::runtime::{ActivePacks, TenantRuntime};
 use crate::storage::{
     DynSessionStore, DynStateStore, new_session_store, new_state_store, session_host_from,
@@ -33,6 +35,7 @@ pub struct HostBuilder {
     #[cfg(feature = "telemetry")]
     telemetry: Option<TelemetryCfg>,
     wasi_policy: RunnerWasiPolicy,
+    extensions: EngineExtensions,
 }
 
 impl HostBuilder {
@@ -42,6 +45,7 @@ impl HostBuilder {
             #[cfg(feature = "telemetry")]
             telemetry: None,
             wasi_policy: RunnerWasiPolicy::default(),
+            extensions: EngineExtensions::default(),
         }
     }
 
@@ -61,6 +65,31 @@ impl HostBuilder {
         self
     }
 
+    /// Make a custom node component available to every tenant's flows.
+    pub fn with_node_handler(
+        mut self,
+        component: impl Into<String>,
+        handler: Arc<dyn NodeHandler>,
+    ) -> Self {
+        self.extensions.nodes.register_arc(component, handler);
+        self
+    }
+
+    pub fn with_node_registry(mut self, nodes: NodeRegistry) -> Self {
+        self.extensions.nodes = nodes;
+        self
+    }
+
+    /// Make a Handlebars helper available to every tenant's templates.
+    pub fn with_template_helper(
+        mut self,
+        name: impl Into<String>,
+        helper: Arc<dyn HelperDef + Send + Sync>,
+    ) -> Self {
+        self.extensions.helpers.register_arc(name, helper);
+        self
+    }
+
     pub fn build(self) -> Result<RunnerHost> {
         if self.configs.is_empty() {
             bail!("at least one tenant configuration is required");
@@ -84,6 +113,7 @@ impl HostBuilder {
             session_host,
             state_host,
             wasi_policy,
+            extensions: self.extensions,
             #[cfg(feature = "telemetry")]
             telemetry: self.telemetry,
         })
@@ -106,6 +136,7 @@ pub struct RunnerHost {
     session_host: Arc<dyn SessionHost>,
     state_host: Arc<dyn StateHost>,
     wasi_policy: Arc<RunnerWasiPolicy>,
+    extensions: EngineExtensions,
     #[cfg(feature = "telemetry")]
     telemetry: Option<TelemetryCfg>,
 }
@@ -230,6 +261,10 @@ impl RunnerHost {
         Arc::clone(&self.state_host)
     }
 
+    pub fn engine_extensions(&self) -> EngineExtensions {
+        self.extensions.clone()
+    }
+
     pub fn tenant_configs(&self) -> HashMap<String, Arc<HostConfig>> {
         self.configs.clone()
     }
@@ -262,6 +297,7 @@ impl RunnerHost {
             self.session_store(),
             self.state_store(),
             self.state_host(),
+            self.engine_extensions(),
         )
         .await?;
         let timers = adapt_timer::spawn_timers(Arc::clone(&runtime))?;
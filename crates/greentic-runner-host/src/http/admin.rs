@@ -2,6 +2,7 @@ use axum::Json;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
+use serde::Deserialize;
 use serde_json::json;
 use time::format_description::well_known::Rfc3339;
 
@@ -18,7 +19,7 @@ pub async fn status(AdminGuard: AdminGuard, State(state): State<ServerState>) ->
             let overlays = runtime
                 .overlays()
                 .into_iter()
-                .zip(runtime.overlay_digests().into_iter())
+                .zip(runtime.overlay_digests())
                 .map(|(overlay, digest)| {
                     let meta = overlay.metadata();
                     json!({
@@ -34,6 +35,7 @@ pub async fn status(AdminGuard: AdminGuard, State(state): State<ServerState>) ->
                 "version": metadata.version,
                 "digest": runtime.digest(),
                 "overlays": overlays,
+                "pinned": runtime.state_machine().pinned_engines().digests(),
             })
         })
         .collect::<Vec<_>>();
@@ -74,3 +76,44 @@ pub async fn reload(AdminGuard: AdminGuard, State(state): State<ServerState>) ->
         )
     }
 }
+
+#[derive(Deserialize)]
+pub struct CancelRuns {
+    tenant: String,
+    /// Session key of the runs to cancel; every run of the tenant when omitted.
+    #[serde(default)]
+    session: Option<String>,
+}
+
+pub async fn cancel_runs(
+    AdminGuard: AdminGuard,
+    State(state): State<ServerState>,
+    Json(request): Json<CancelRuns>,
+) -> impl IntoResponse {
+    let Some(runtime) = state.active.load(&request.tenant) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("tenant {} not loaded", request.tenant) })),
+        );
+    };
+    let cancelled = runtime
+        .state_machine()
+        .cancel_runs(request.session.as_deref());
+    tracing::info!(
+        tenant = %request.tenant,
+        session = request.session.as_deref(),
+        cancelled,
+        "flow.runs.cancel.requested"
+    );
+    (
+        StatusCode::OK,
+        Json(json!({
+            "tenant": request.tenant,
+            "session": request.session,
+            "cancelled": cancelled,
+            // Pack nodes trap right away; `mcp.exec` tools already running are only
+            // abandoned, not aborted.
+            "best_effort": true,
+        })),
+    )
+}
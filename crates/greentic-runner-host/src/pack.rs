@@ -4,8 +4,11 @@ use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Instant;
 
-use crate::runtime_wasmtime::{Component, Engine, Linker, ResourceTable, Store, WasmResult};
+use crate::runtime_wasmtime::{
+    Component, Config, Engine, Linker, ResourceTable, Store, UpdateDeadline, WasmResult,
+};
 use anyhow::{Context, Result, anyhow, bail};
 use greentic_flow::ir::{FlowIR, NodeIR, RouteIR};
 use greentic_interfaces::host_import_v0_2;
@@ -15,7 +18,10 @@ use greentic_interfaces::host_import_v0_2::greentic::host_import::imports::{
 };
 use greentic_interfaces::host_import_v0_6::{self, iface_types, state, types};
 use greentic_interfaces::pack_export_v0_2;
-use greentic_interfaces::pack_export_v0_2::exports::greentic::pack_export::exports::FlowInfo;
+use greentic_interfaces::pack_export_v0_2::exports::greentic::pack_export::exports::{
+    Cloud, DeploymentCtx, FlowInfo, IfaceError as ExportIfaceError, Platform, RunOpts, RunResult,
+    SchemaDoc, TenantCtx as ExportTenantCtx,
+};
 #[cfg(feature = "mcp")]
 use greentic_mcp::{ExecConfig, ExecError, ExecRequest};
 use greentic_session::SessionKey as StoreSessionKey;
@@ -30,11 +36,17 @@ use serde_cbor;
 use serde_json::{self, Value, json};
 use serde_yaml_bw as serde_yaml;
 use tokio::fs;
+use tokio_util::sync::CancellationToken;
 use wasmparser::{Parser, Payload};
 use zip::ZipArchive;
 
 use crate::imports;
-use crate::runner::mocks::{HttpDecision, HttpMockRequest, HttpMockResponse, MockLayer};
+use crate::runner::errors::{ErrorKind, NodeError};
+use crate::runner::flow_routes::FlowRoutes;
+use crate::runner::http_request::{self, HttpCall, HttpFetchError};
+use crate::runner::journal::{HostCall, HostCallLog};
+use crate::runner::mocks::{HttpMockResponse, MockLayer};
+use crate::runner::templating::Redactor;
 
 use crate::config::HostConfig;
 use crate::storage::state::STATE_PREFIX;
@@ -56,6 +68,71 @@ pub struct PackRuntime {
     wasi_policy: Arc<RunnerWasiPolicy>,
 }
 
+/// Flow IR plus the host-side routing table (conditions, defaults) loaded alongside it.
+#[derive(Debug, Clone)]
+pub struct PackFlow {
+    pub ir: FlowIR,
+    pub routes: FlowRoutes,
+    pub schemas: FlowSchemas,
+}
+
+impl PackFlow {
+    pub fn from_ir(ir: FlowIR) -> Self {
+        let routes = FlowRoutes::from_ir(&ir);
+        let schemas = FlowSchemas::from_parameters(&ir.parameters);
+        Self {
+            ir,
+            routes,
+            schemas,
+        }
+    }
+}
+
+/// JSON Schemas a flow declares for its input and output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlowSchemas {
+    pub input: Option<Value>,
+    pub output: Option<Value>,
+}
+
+impl FlowSchemas {
+    /// Read `input_schema` / `output_schema` from the flow document parameters.
+    pub fn from_parameters(parameters: &Value) -> Self {
+        Self {
+            input: parameters.get("input_schema").cloned(),
+            output: parameters.get("output_schema").cloned(),
+        }
+    }
+
+    /// Schemas from the pack's `get-flow-schema` export; empty strings mean "not declared".
+    fn from_export(flow_id: &str, doc: &SchemaDoc) -> Self {
+        let parse = |raw: &str, which: &str| -> Option<Value> {
+            if raw.trim().is_empty() {
+                return None;
+            }
+            match serde_json::from_str(raw) {
+                Ok(schema) => Some(schema),
+                Err(err) => {
+                    tracing::warn!(flow_id, error = %err, "ignoring invalid {which} schema from pack");
+                    None
+                }
+            }
+        };
+        Self {
+            input: parse(&doc.input_jsonschema, "input"),
+            output: parse(&doc.output_jsonschema, "output"),
+        }
+    }
+
+    /// Fill in whatever `self` does not declare from `other`.
+    fn or(self, other: Self) -> Self {
+        Self {
+            input: self.input.or(other.input),
+            output: self.output.or(other.output),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FlowDescriptor {
     pub id: String,
@@ -76,6 +153,10 @@ pub struct HostState {
     session_store: Option<DynSessionStore>,
     state_store: Option<DynStateStore>,
     mocks: Option<Arc<MockLayer>>,
+    /// Collects host imports for the execution journal.
+    host_calls: Option<HostCallLog>,
+    /// Secret values handed to the pack while host calls are logged; masked in the log.
+    pack_secrets: Vec<String>,
 }
 
 impl HostState {
@@ -98,9 +179,40 @@ impl HostState {
             session_store,
             state_store,
             mocks,
+            host_calls: None,
+            pack_secrets: Vec::new(),
         })
     }
 
+    /// Log every `http_fetch` and `mcp.exec` import made through this state into `log`.
+    pub fn with_host_calls(mut self, log: Option<HostCallLog>) -> Self {
+        self.host_calls = log;
+        self
+    }
+
+    /// Log a host import for the journal. Secrets the pack fetched during the call and
+    /// credential headers are masked, so they never reach a transcript.
+    fn record_host_call(&self, import: &str, request: Value, response: Value) {
+        if let Some(log) = &self.host_calls {
+            let redactor = Redactor::new(self.pack_secrets.iter().cloned());
+            log.lock().push(HostCall {
+                import: import.to_string(),
+                request: mask_credential_headers(redactor.redact(request)),
+                response: mask_credential_headers(redactor.redact(response)),
+            });
+        }
+    }
+
+    /// [`Self::get_secret`] for the pack's `secrets-get` import; the value is remembered so
+    /// logged host calls can mask it.
+    fn pack_secret(&mut self, key: &str) -> Result<String> {
+        let value = self.get_secret(key)?;
+        if self.host_calls.is_some() {
+            self.pack_secrets.push(value.clone());
+        }
+        Ok(value)
+    }
+
     pub fn get_secret(&self, key: &str) -> Result<String> {
         if !self.config.secrets_policy.is_allowed(key) {
             bail!("secret {key} is not permitted by bindings policy");
@@ -337,7 +449,7 @@ impl host_import_v0_6::HostImports for HostState {
         key: String,
         _ctx: Option<types::TenantCtx>,
     ) -> WasmResult<Result<String, types::IfaceError>> {
-        Ok(self.get_secret(&key).map_err(|err| {
+        Ok(self.pack_secret(&key).map_err(|err| {
             tracing::warn!(secret = %key, error = %err, "secret lookup denied");
             types::IfaceError::Denied
         }))
@@ -416,13 +528,16 @@ impl host_import_v0_6::HostImports for HostState {
                     None
                 }
             };
+            let logged = json!({ "component": component, "action": action, "args": args });
             let request = ExecRequest {
                 component: component.clone(),
                 action: action.clone(),
                 args,
                 tenant,
             };
-            match greentic_mcp::exec(request, &exec_config) {
+            let result = greentic_mcp::exec(request, &exec_config);
+            self.record_host_call("mcp.exec", logged, exec_result_json(&result));
+            match result {
                 Ok(value) => match serde_json::to_string(&value) {
                     Ok(body) => Ok(Ok(body)),
                     Err(err) => {
@@ -588,7 +703,7 @@ impl greentic_interfaces::host_import_v0_2::HostImports for HostState {
         key: String,
         _ctx: Option<LegacyTenantCtx>,
     ) -> WasmResult<Result<String, LegacyIfaceError>> {
-        Ok(self.get_secret(&key).map_err(|err| {
+        Ok(self.pack_secret(&key).map_err(|err| {
             tracing::warn!(secret = %key, error = %err, "secret lookup denied");
             LegacyIfaceError::Denied
         }))
@@ -642,6 +757,7 @@ impl greentic_interfaces::host_import_v0_2::HostImports for HostState {
 
             let tenant = ctx.map(|ctx| map_legacy_tenant_ctx(ctx, &self.default_env));
 
+            let logged = json!({ "component": tool, "action": action, "args": args });
             let request = ExecRequest {
                 component: tool.clone(),
                 action: action.clone(),
@@ -664,7 +780,9 @@ impl greentic_interfaces::host_import_v0_2::HostImports for HostState {
                 };
             }
 
-            match greentic_mcp::exec(request, &exec_config) {
+            let result = greentic_mcp::exec(request, &exec_config);
+            self.record_host_call("mcp.exec", logged, exec_result_json(&result));
+            match result {
                 Ok(value) => match serde_json::to_string(&value) {
                     Ok(body) => Ok(Ok(body)),
                     Err(err) => {
@@ -690,48 +808,13 @@ impl greentic_interfaces::host_import_v0_2::HostImports for HostState {
         req: LegacyHttpRequest,
         _ctx: Option<LegacyTenantCtx>,
     ) -> WasmResult<Result<LegacyHttpResponse, LegacyIfaceError>> {
-        if !self.config.http_enabled {
-            tracing::warn!(url = %req.url, "http fetch denied by policy");
-            return Ok(Err(LegacyIfaceError::Denied));
-        }
-
-        let mut mock_state = None;
-        let raw_body = req.body.clone();
-        if let Some(mock) = &self.mocks
-            && let Ok(meta) = HttpMockRequest::new(
-                &req.method,
-                &req.url,
-                raw_body.as_deref().map(|body| body.as_bytes()),
-            )
-        {
-            match mock.http_begin(&meta) {
-                HttpDecision::Mock(response) => {
-                    let http = LegacyHttpResponse::from(&response);
-                    return Ok(Ok(http));
-                }
-                HttpDecision::Deny(reason) => {
-                    tracing::warn!(url = %req.url, reason = %reason, "http fetch blocked by mocks");
-                    return Ok(Err(LegacyIfaceError::Denied));
-                }
-                HttpDecision::Passthrough { record } => {
-                    mock_state = Some((meta, record));
-                }
-            }
-        }
-
-        let method = req.method.parse().unwrap_or(reqwest::Method::GET);
-        let mut builder = self.http_client.request(method, &req.url);
-
+        let mut headers = BTreeMap::new();
         if let Some(headers_json) = req.headers_json.as_ref() {
             match serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(headers_json) {
                 Ok(map) => {
                     for (key, value) in map {
-                        if let Some(val) = value.as_str()
-                            && let Ok(header) =
-                                reqwest::header::HeaderName::from_bytes(key.as_bytes())
-                            && let Ok(header_value) = reqwest::header::HeaderValue::from_str(val)
-                        {
-                            builder = builder.header(header, header_value);
+                        if let Some(val) = value.as_str() {
+                            headers.insert(key, val.to_string());
                         }
                     }
                 }
@@ -740,45 +823,44 @@ impl greentic_interfaces::host_import_v0_2::HostImports for HostState {
                 }
             }
         }
+        let call = HttpCall {
+            method: req.method,
+            url: req.url,
+            headers,
+            body: req.body,
+            timeout: None,
+        };
 
-        if let Some(body) = raw_body.clone() {
-            builder = builder.body(body);
-        }
-
-        let response = match builder.send() {
-            Ok(resp) => resp,
+        let result = http_request::fetch_blocking(
+            self.config.http_enabled,
+            self.mocks.as_deref(),
+            &self.http_client,
+            &call,
+        );
+        self.record_host_call(
+            "http_fetch",
+            json!({
+                "method": call.method,
+                "url": call.url,
+                "headers": call.headers,
+                "body": call.body,
+            }),
+            match &result {
+                Ok(response) => json!(response),
+                Err(err) => json!({ "error": err.to_string() }),
+            },
+        );
+        match result {
+            Ok(response) => Ok(Ok(LegacyHttpResponse::from(&response))),
+            Err(err @ HttpFetchError::Denied { .. }) => {
+                tracing::warn!(url = %call.url, error = %err, "http fetch denied");
+                Ok(Err(LegacyIfaceError::Denied))
+            }
             Err(err) => {
-                tracing::error!(url = %req.url, error = %err, "http fetch failed");
-                return Ok(Err(LegacyIfaceError::Unavailable));
+                tracing::error!(url = %call.url, error = %err, "http fetch failed");
+                Ok(Err(LegacyIfaceError::Unavailable))
             }
-        };
-
-        let status = response.status().as_u16();
-        let headers_map = response
-            .headers()
-            .iter()
-            .map(|(k, v)| {
-                (
-                    k.as_str().to_string(),
-                    v.to_str().unwrap_or_default().to_string(),
-                )
-            })
-            .collect::<BTreeMap<_, _>>();
-        let headers_json = serde_json::to_string(&headers_map).ok();
-        let body = response.text().ok();
-
-        if let Some((meta, true)) = mock_state.take()
-            && let Some(mock) = &self.mocks
-        {
-            let recorded = HttpMockResponse::new(status, headers_map.clone(), body.clone());
-            mock.http_record(&meta, &recorded);
         }
-
-        Ok(Ok(LegacyHttpResponse {
-            status,
-            headers_json,
-            body,
-        }))
     }
 }
 
@@ -812,7 +894,8 @@ impl PackRuntime {
             verify::verify_pack(path).await?;
             tracing::info!(pack_path = %path.display(), "pack verification complete");
         }
-        let engine = Engine::default();
+        // Epoch interruption lets a cancelled run trap the Wasm call it is blocked on.
+        let engine = Engine::new(Config::new().epoch_interruption(true))?;
         let wasm_bytes = fs::read(path).await?;
         let mut metadata =
             PackMetadata::from_wasm(&wasm_bytes).unwrap_or_else(|| PackMetadata::fallback(path));
@@ -862,16 +945,7 @@ impl PackRuntime {
             .component
             .as_ref()
             .ok_or_else(|| anyhow!("pack component unavailable"))?;
-        let host_state = HostState::new(
-            Arc::clone(&self.config),
-            self.mocks.clone(),
-            self.session_store.clone(),
-            self.state_store.clone(),
-        )?;
-        let mut store = Store::new(
-            &self.engine,
-            ComponentState::new(host_state, Arc::clone(&self.wasi_policy))?,
-        );
+        let mut store = self.new_store(None, None, None)?;
         let mut linker = Linker::new(&self.engine);
         imports::register_all(&mut linker)?;
         let bindings = pack_export_v0_2::PackExports::instantiate(&mut store, component, &linker)?;
@@ -899,38 +973,140 @@ impl PackRuntime {
         Ok(flows)
     }
 
-    #[allow(dead_code)]
     pub async fn run_flow(
         &self,
-        _flow_id: &str,
-        _input: serde_json::Value,
+        flow_id: &str,
+        input: serde_json::Value,
     ) -> Result<serde_json::Value> {
-        // TODO: dispatch flow execution via Wasmtime
-        Ok(serde_json::json!({}))
+        self.run_export(flow_id, &input, None, None, None)
     }
 
-    pub fn load_flow_ir(&self, flow_id: &str) -> Result<greentic_flow::ir::FlowIR> {
-        if let Some(archive) = &self.archive {
-            return archive
-                .flows
-                .get(flow_id)
-                .cloned()
-                .ok_or_else(|| anyhow!("flow '{flow_id}' not found in archive"));
-        }
+    /// Node components this pack implements, as declared in its manifest.
+    pub fn node_components(&self) -> &[String] {
+        &self.metadata.node_components
+    }
+
+    /// Template helpers this pack declares in its manifest.
+    pub fn template_helpers(&self) -> &BTreeMap<String, String> {
+        &self.metadata.template_helpers
+    }
+
+    /// Call the pack's `run-flow` export for `name` (a flow or a node component) with the host
+    /// imports linked in. Blocks for the duration of the Wasm call; once `cancel` fires or
+    /// `deadline` passes, the call traps at the first epoch check after [`Self::interrupt`].
+    /// The time left is also passed to the pack as `timeout_ms`, but only the trap enforces it.
+    pub fn run_export(
+        &self,
+        name: &str,
+        input: &Value,
+        deadline: Option<Instant>,
+        cancel: Option<CancellationToken>,
+        host_calls: Option<HostCallLog>,
+    ) -> Result<Value> {
         let component = self
             .component
             .as_ref()
             .ok_or_else(|| anyhow!("pack component unavailable"))?;
+        let mut store = self.new_store(cancel, deadline, host_calls)?;
+        let mut linker = Linker::new(&self.engine);
+        imports::register_all(&mut linker)?;
+        let bindings = pack_export_v0_2::PackExports::instantiate(&mut store, component, &linker)?;
+        let exports = bindings.greentic_pack_export_exports();
+        let export_name = name.to_string();
+        let input_json = serde_json::to_string(input)?;
+        let opts = RunOpts {
+            tenant: Some(ExportTenantCtx {
+                tenant: self.config.tenant.clone(),
+                team: None,
+                user: None,
+                deployment: DeploymentCtx {
+                    cloud: Cloud::Local,
+                    region: None,
+                    platform: Platform::Other,
+                    runtime: std::env::var("GREENTIC_ENV").ok(),
+                },
+                trace_id: None,
+            }),
+            timeout_ms: deadline.map(|deadline| {
+                let left = deadline.saturating_duration_since(Instant::now());
+                left.as_millis().min(u32::MAX as u128) as u32
+            }),
+            fuel_units: None,
+        };
+        match exports.call_run_flow(&mut store, &export_name, &input_json, Some(&opts))? {
+            Ok(result) => run_result_output(name, result),
+            Err(err) => Err(NodeError::new(
+                export_error_kind(&err),
+                anyhow!("pack run_flow({name}) failed: {err:?}"),
+            )
+            .into()),
+        }
+    }
+
+    /// Make in-flight Wasm calls check their cancellation token and deadline.
+    pub fn interrupt(&self) {
+        self.engine.increment_epoch();
+    }
+
+    /// A store for one call into the pack. Epoch ticks trap the call when `cancel` has fired
+    /// or `deadline` has passed and are ignored otherwise; host imports are logged to
+    /// `host_calls` when set.
+    fn new_store(
+        &self,
+        cancel: Option<CancellationToken>,
+        deadline: Option<Instant>,
+        host_calls: Option<HostCallLog>,
+    ) -> Result<Store<ComponentState>> {
         let host_state = HostState::new(
             Arc::clone(&self.config),
             self.mocks.clone(),
             self.session_store.clone(),
             self.state_store.clone(),
-        )?;
+        )?
+        .with_host_calls(host_calls);
         let mut store = Store::new(
             &self.engine,
             ComponentState::new(host_state, Arc::clone(&self.wasi_policy))?,
         );
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(move |_| {
+            if cancel.as_ref().is_some_and(CancellationToken::is_cancelled) {
+                return Err(NodeError::new(
+                    ErrorKind::Cancelled,
+                    anyhow!("pack call interrupted: run cancelled"),
+                )
+                .into());
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(NodeError::new(
+                    ErrorKind::Timeout,
+                    anyhow!("pack call interrupted: deadline exceeded"),
+                )
+                .into());
+            }
+            Ok(UpdateDeadline::Continue(1))
+        });
+        Ok(store)
+    }
+
+    pub fn load_flow_ir(&self, flow_id: &str) -> Result<greentic_flow::ir::FlowIR> {
+        self.load_flow(flow_id).map(|flow| flow.ir)
+    }
+
+    /// Load a flow together with the routing metadata the IR cannot express.
+    pub fn load_flow(&self, flow_id: &str) -> Result<PackFlow> {
+        if let Some(archive) = &self.archive {
+            return archive
+                .flows
+                .get(flow_id)
+                .map(|ir| PackFlow::from_ir(ir.clone()))
+                .ok_or_else(|| anyhow!("flow '{flow_id}' not found in archive"));
+        }
+        let component = self
+            .component
+            .as_ref()
+            .ok_or_else(|| anyhow!("pack component unavailable"))?;
+        let mut store = self.new_store(None, None, None)?;
         let mut linker = Linker::new(&self.engine);
         imports::register_all(&mut linker)?;
         let bindings = pack_export_v0_2::PackExports::instantiate(&mut store, component, &linker)?;
@@ -940,11 +1116,27 @@ impl PackRuntime {
             Ok(doc) => doc,
             Err(err) => bail!("pack flow_metadata({flow_id}) failed: {err:?}"),
         };
-        let flow_doc: greentic_flow::model::FlowDoc = serde_json::from_str(&metadata)
+        let raw_doc: Value = serde_json::from_str(&metadata)
             .or_else(|_| serde_yaml::from_str(&metadata))
             .with_context(|| format!("failed to parse flow metadata for {flow_id}"))?;
+        let flow_doc: greentic_flow::model::FlowDoc = serde_json::from_value(raw_doc.clone())
+            .with_context(|| format!("failed to decode flow metadata for {flow_id}"))?;
         let ir = greentic_flow::to_ir(flow_doc)?;
-        Ok(ir)
+        let routes = FlowRoutes::from_document(&ir, &raw_doc);
+        // Schemas exported by the pack take precedence over the flow document parameters.
+        let exported = match exports.call_get_flow_schema(&mut store, &flow_name)? {
+            Ok(doc) => FlowSchemas::from_export(flow_id, &doc),
+            Err(err) => {
+                tracing::debug!(flow_id, error = ?err, "pack does not export a flow schema");
+                FlowSchemas::default()
+            }
+        };
+        let schemas = exported.or(FlowSchemas::from_parameters(&ir.parameters));
+        Ok(PackFlow {
+            ir,
+            routes,
+            schemas,
+        })
     }
 
     pub fn metadata(&self) -> &PackMetadata {
@@ -1024,6 +1216,51 @@ impl ArchiveFlows {
     }
 }
 
+/// Headers whose values are credentials, compared case-insensitively.
+const CREDENTIAL_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+/// Mask the values of credential headers in a logged request or response.
+fn mask_credential_headers(mut value: Value) -> Value {
+    if let Some(headers) = value.get_mut("headers").and_then(Value::as_object_mut) {
+        for (name, header) in headers.iter_mut() {
+            if CREDENTIAL_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                *header = Value::String(Redactor::MASK.to_string());
+            }
+        }
+    }
+    value
+}
+
+fn export_error_kind(err: &ExportIfaceError) -> ErrorKind {
+    match err {
+        ExportIfaceError::InvalidArg | ExportIfaceError::NotFound => ErrorKind::Permanent,
+        ExportIfaceError::Denied => ErrorKind::Policy,
+        ExportIfaceError::Unavailable | ExportIfaceError::Internal => ErrorKind::Transient,
+    }
+}
+
+/// Decode a `run-flow` result; a reported error is classified from the result status.
+fn run_result_output(name: &str, result: RunResult) -> Result<Value> {
+    if let Some(error) = result.error {
+        return Err(NodeError::new(
+            ErrorKind::from_code(&result.status),
+            anyhow!("pack component {name} failed ({}): {error}", result.status),
+        )
+        .into());
+    }
+    match result.output_json {
+        Some(raw) => serde_json::from_str(&raw)
+            .with_context(|| format!("pack component {name} returned invalid JSON")),
+        None => Ok(Value::Null),
+    }
+}
+
 fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>> {
     let mut file = archive
         .by_name(name)
@@ -1064,6 +1301,12 @@ pub struct PackMetadata {
     pub version: String,
     #[serde(default)]
     pub entry_flows: Vec<String>,
+    /// Node components the pack implements behind its `run-flow` export.
+    #[serde(default)]
+    pub node_components: Vec<String>,
+    /// Handlebars helpers the pack defines as template snippets, keyed by helper name.
+    #[serde(default)]
+    pub template_helpers: BTreeMap<String, String>,
 }
 
 impl PackMetadata {
@@ -1101,6 +1344,10 @@ impl PackMetadata {
             entry_flows: Vec<String>,
             #[serde(default)]
             flows: Vec<RawFlow>,
+            #[serde(default)]
+            node_components: Vec<String>,
+            #[serde(default)]
+            template_helpers: BTreeMap<String, String>,
         }
 
         #[derive(Deserialize)]
@@ -1119,6 +1366,8 @@ impl PackMetadata {
             pack_id: manifest.pack_id,
             version: manifest.version,
             entry_flows,
+            node_components: manifest.node_components,
+            template_helpers: manifest.template_helpers,
         })
     }
 
@@ -1131,6 +1380,8 @@ impl PackMetadata {
             pack_id,
             version: "0.0.0".to_string(),
             entry_flows: Vec::new(),
+            node_components: Vec::new(),
+            template_helpers: BTreeMap::new(),
         }
     }
 
@@ -1148,10 +1399,21 @@ impl PackMetadata {
             pack_id: manifest.meta.pack_id.clone(),
             version: manifest.meta.version.to_string(),
             entry_flows,
+            // Archive packs run without their component, so they cannot serve node calls.
+            node_components: Vec::new(),
+            template_helpers: BTreeMap::new(),
         }
     }
 }
 
+#[cfg(feature = "mcp")]
+fn exec_result_json(result: &Result<Value, ExecError>) -> Value {
+    match result {
+        Ok(value) => value.clone(),
+        Err(err) => json!({ "error": err.to_string() }),
+    }
+}
+
 impl From<&HttpMockResponse> for LegacyHttpResponse {
     fn from(value: &HttpMockResponse) -> Self {
         let headers_json = serde_json::to_string(&value.headers).ok();
@@ -1162,3 +1424,129 @@ impl From<&HttpMockResponse> for LegacyHttpResponse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::errors::classify;
+
+    #[test]
+    fn logged_host_calls_mask_secrets_the_pack_fetched() {
+        use crate::runner::mocks::{MocksConfig, SecretsMock};
+
+        let mut config = HostConfig::load_from_path(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../examples/bindings/default.bindings.yaml"),
+        )
+        .unwrap();
+        config.http_enabled = false;
+        let mocks = MockLayer::new(
+            MocksConfig {
+                secrets: Some(SecretsMock {
+                    map: [("TELEGRAM_BOT_TOKEN".to_string(), "tok-123".to_string())].into(),
+                }),
+                ..MocksConfig::default()
+            },
+            &std::env::temp_dir(),
+        )
+        .unwrap();
+        let log = HostCallLog::default();
+        let mut host = HostState::new(Arc::new(config), Some(Arc::new(mocks)), None, None)
+            .unwrap()
+            .with_host_calls(Some(log.clone()));
+
+        let token = host_import_v0_6::HostImports::secrets_get(
+            &mut host,
+            "TELEGRAM_BOT_TOKEN".into(),
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        let _ = greentic_interfaces::host_import_v0_2::HostImports::http_fetch(
+            &mut host,
+            LegacyHttpRequest {
+                method: "POST".into(),
+                url: format!("https://api.telegram.org/bot{token}/sendMessage"),
+                headers_json: Some(
+                    json!({ "Authorization": "Basic c2VjcmV0", "X-Token": token }).to_string(),
+                ),
+                body: Some(format!("{{\"token\":\"{token}\"}}")),
+            },
+            None,
+        )
+        .unwrap();
+
+        let calls = log.lock();
+        assert_eq!(calls.len(), 1);
+        let logged = serde_json::to_string(&calls[0]).unwrap();
+        assert!(!logged.contains("tok-123"), "{logged}");
+        assert!(!logged.contains("c2VjcmV0"), "{logged}");
+        assert_eq!(
+            calls[0].request["url"],
+            json!("https://api.telegram.org/bot[REDACTED]/sendMessage")
+        );
+    }
+
+    #[test]
+    fn manifest_declares_node_components() {
+        let bytes = serde_cbor::to_vec(&json!({
+            "pack_id": "acme.crm",
+            "version": "1.0.0",
+            "flows": [{ "id": "main" }],
+            "node_components": ["crm.lookup", "crm.score"],
+            "template_helpers": { "money": "{{args.0}} EUR" },
+        }))
+        .unwrap();
+        let meta = PackMetadata::from_bytes(&bytes).unwrap();
+        assert_eq!(meta.entry_flows, vec!["main".to_string()]);
+        assert_eq!(meta.node_components, vec!["crm.lookup", "crm.score"]);
+        assert_eq!(meta.template_helpers["money"], "{{args.0}} EUR");
+    }
+
+    #[test]
+    fn exported_schemas_override_flow_parameters() {
+        let from_doc = FlowSchemas::from_parameters(&json!({
+            "input_schema": { "type": "object", "required": ["city"] },
+            "output_schema": { "type": "string" },
+        }));
+        let exported = FlowSchemas::from_export(
+            "weather",
+            &SchemaDoc {
+                input_jsonschema: r#"{"type":"object"}"#.into(),
+                output_jsonschema: String::new(),
+            },
+        );
+        let merged = exported.or(from_doc);
+        assert_eq!(merged.input, Some(json!({ "type": "object" })));
+        assert_eq!(merged.output, Some(json!({ "type": "string" })));
+    }
+
+    #[test]
+    fn run_results_decode_output_or_classified_error() {
+        let ok = RunResult {
+            status: "ok".into(),
+            output_json: Some(r#"{"score":7}"#.into()),
+            error: None,
+            logs_json: None,
+            metrics_json: None,
+        };
+        assert_eq!(
+            run_result_output("crm.score", ok).unwrap(),
+            json!({ "score": 7 })
+        );
+
+        let failed = RunResult {
+            status: "unavailable".into(),
+            output_json: None,
+            error: Some("crm offline".into()),
+            logs_json: None,
+            metrics_json: None,
+        };
+        let err = run_result_output("crm.score", failed).unwrap_err();
+        assert_eq!(classify(&err), ErrorKind::Transient);
+        assert_eq!(
+            err.to_string(),
+            "pack component crm.score failed (unavailable): crm offline"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Flow runs in progress for a tenant, so they can be cancelled (`POST /admin/runs/cancel`).
+//!
+//! Each run registers its [`CancellationToken`] under its session key and drops out of the
+//! registry when its [`ActiveRun`] guard is dropped, however the run ends.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Clone, Default)]
+pub struct ActiveRuns {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: AtomicU64,
+    runs: Mutex<HashMap<u64, (String, CancellationToken)>>,
+}
+
+impl ActiveRuns {
+    /// Register a run for `session`; it stays cancellable until the guard is dropped.
+    pub fn start(&self, session: &str) -> ActiveRun {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::new();
+        self.inner
+            .runs
+            .lock()
+            .insert(id, (session.to_string(), token.clone()));
+        ActiveRun {
+            id,
+            token,
+            runs: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Cancel the runs of `session`, or every run when `session` is `None`. Returns how many
+    /// runs were signalled.
+    pub fn cancel(&self, session: Option<&str>) -> usize {
+        let runs = self.inner.runs.lock();
+        let mut cancelled = 0;
+        for (run_session, token) in runs.values() {
+            if session.is_none_or(|session| session == run_session) {
+                token.cancel();
+                cancelled += 1;
+            }
+        }
+        cancelled
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.runs.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Registration of a single run; unregisters on drop.
+pub struct ActiveRun {
+    id: u64,
+    token: CancellationToken,
+    runs: Arc<Inner>,
+}
+
+impl ActiveRun {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for ActiveRun {
+    fn drop(&mut self) {
+        self.runs.runs.lock().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancels_by_session_and_unregisters_on_drop() {
+        let runs = ActiveRuns::default();
+        let first = runs.start("s1");
+        let second = runs.start("s2");
+        assert_eq!(runs.cancel(Some("s1")), 1);
+        assert!(first.token().is_cancelled());
+        assert!(!second.token().is_cancelled());
+
+        drop(first);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs.cancel(None), 1);
+        assert!(second.token().is_cancelled());
+        drop(second);
+        assert!(runs.is_empty());
+    }
+}
@@ -1,4 +1,6 @@
-use anyhow::{Result, bail};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
 use axum::extract::Json;
 use axum::http::StatusCode;
 use chrono::{DateTime, Utc};
@@ -10,6 +12,7 @@ use crate::ingress::{
     ProviderIds, build_canonical_payload, canonical_session_key, default_metadata, empty_entities,
 };
 use crate::routing::TenantRuntimeHandle;
+use crate::runner::engine::egress_channel;
 use crate::runtime::TenantRuntime;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -100,10 +103,52 @@ pub async fn telegram_webhook(
     }
     .canonicalize();
 
-    match runtime.state_machine().handle(envelope).await {
+    // Replies emitted mid-flow are sent right away; the final response follows once the
+    // flow is done.
+    let (egress, mut emitted) = egress_channel();
+    let streamer = {
+        let runtime = Arc::clone(&runtime);
+        let chat_id = message.chat.id;
+        tokio::spawn(async move {
+            let mut sent = 0;
+            while let Some(item) = emitted.recv().await {
+                for text in collect_text_responses(&item.payload) {
+                    send_telegram_message(runtime.as_ref(), chat_id, &text).await?;
+                    sent += 1;
+                }
+            }
+            Ok::<_, anyhow::Error>(sent)
+        })
+    };
+    let result = runtime
+        .state_machine()
+        .handle_streaming(envelope, egress)
+        .await;
+    let streamed = match streamer.await {
+        Ok(Ok(sent)) => sent,
+        Ok(Err(err)) => {
+            tracing::error!(
+                flow_id = %flow.id,
+                update_id = update.update_id,
+                error = %err,
+                "failed to send telegram message"
+            );
+            return remember_status(runtime.as_ref(), update.update_id, StatusCode::BAD_GATEWAY);
+        }
+        Err(err) => {
+            tracing::error!(update_id = update.update_id, error = %err, "telegram reply task failed");
+            return remember_status(
+                runtime.as_ref(),
+                update.update_id,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            );
+        }
+    };
+
+    match result {
         Ok(response) => {
             let replies = collect_text_responses(&response);
-            if replies.is_empty() {
+            if replies.is_empty() && streamed == 0 {
                 tracing::info!(
                     flow_id = %flow.id,
                     update_id = update.update_id,
@@ -133,7 +178,7 @@ pub async fn telegram_webhook(
             tracing::info!(
                 flow_id = %flow.id,
                 update_id = update.update_id,
-                replies = replies.len(),
+                replies = streamed + replies.len(),
                 "flow completed"
             );
             remember_status(runtime.as_ref(), update.update_id, StatusCode::OK)
@@ -177,6 +222,36 @@ async fn send_telegram_message(runtime: &TenantRuntime, chat_id: i64, text: &str
     Ok(())
 }
 
+/// Send the text replies in `payload` to the conversation of `envelope`, for flows that
+/// resume without an inbound request to answer. Returns how many messages were sent.
+pub(crate) async fn send_replies(
+    runtime: &TenantRuntime,
+    envelope: &IngressEnvelope,
+    payload: &Value,
+) -> Result<usize> {
+    let replies = collect_text_responses(payload);
+    if replies.is_empty() {
+        return Ok(0);
+    }
+    match envelope.provider.as_deref() {
+        Some("telegram") => {
+            let chat_id = envelope
+                .conversation
+                .as_deref()
+                .and_then(|conversation| conversation.parse::<i64>().ok())
+                .context("telegram envelope has no chat id")?;
+            for text in &replies {
+                send_telegram_message(runtime, chat_id, text).await?;
+            }
+            Ok(replies.len())
+        }
+        provider => bail!(
+            "provider {} has no outbound channel",
+            provider.unwrap_or("unknown")
+        ),
+    }
+}
+
 fn remember_status(runtime: &TenantRuntime, update_id: i64, status: StatusCode) -> StatusCode {
     let mut cache = runtime.telegram_cache().lock();
     cache.put(update_id, status);
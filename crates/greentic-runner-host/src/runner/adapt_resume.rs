@@ -0,0 +1,46 @@
+use axum::Json;
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde_json::{Value, json};
+
+use crate::routing::TenantRuntimeHandle;
+
+/// `POST /resume/{token}`: resume the waiting session behind `token`. The JSON body (if any)
+/// becomes the input of the node after the `session.wait`.
+pub async fn resume(
+    TenantRuntimeHandle { tenant, runtime }: TenantRuntimeHandle,
+    Path(token): Path<String>,
+    body: axum::body::Bytes,
+) -> Response {
+    let input = if body.is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(value) => value,
+            Err(_) => return error(StatusCode::BAD_REQUEST, "body must be JSON"),
+        }
+    };
+
+    match runtime
+        .state_machine()
+        .resume_with_token(&token, input)
+        .await
+    {
+        Ok(Some(output)) => (StatusCode::OK, Json(output)).into_response(),
+        Ok(None) => error(StatusCode::NOT_FOUND, "no session waiting for this token"),
+        Err(err) => {
+            let chain = err.chain().map(|e| e.to_string()).collect::<Vec<_>>();
+            tracing::error!(
+                tenant = %tenant,
+                error.cause_chain = ?chain,
+                "resume by token failed"
+            );
+            error(StatusCode::INTERNAL_SERVER_ERROR, "resume failed")
+        }
+    }
+}
+
+fn error(status: StatusCode, message: &'static str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
@@ -9,11 +9,13 @@ use serde_json::json;
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
+use super::wait_timeouts::spawn_wait_timeouts;
 use crate::engine::runtime::IngressEnvelope;
 use crate::runtime::TenantRuntime;
 
+/// Spawn the tenant's cron timers plus the poller for `session.wait` timeouts.
 pub fn spawn_timers(runtime: Arc<TenantRuntime>) -> Result<Vec<JoinHandle<()>>> {
-    let mut handles = Vec::new();
+    let mut handles = vec![spawn_wait_timeouts(&runtime)];
 
     for timer in runtime.config().timers.clone() {
         let cron_expr = timer.cron.clone();
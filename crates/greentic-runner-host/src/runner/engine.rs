@@ -2,23 +2,44 @@ use std::collections::HashMap;
 use std::env;
 use std::error::Error as StdError;
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result, anyhow, bail};
-use greentic_flow::ir::{FlowIR, NodeIR};
+use futures::future::try_join_all;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use greentic_flow::ir::NodeIR;
 #[cfg(feature = "mcp")]
 use greentic_mcp::{ExecConfig, ExecRequest};
 #[cfg(feature = "mcp")]
 use greentic_types::TenantCtx as TypesTenantCtx;
 use handlebars::Handlebars;
+use indexmap::IndexMap;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map as JsonMap, Value, json};
+use tokio::sync::mpsc;
 use tokio::task;
+use tokio_util::sync::CancellationToken;
 
+#[cfg(feature = "mcp")]
+use super::errors::classify_exec;
+use super::errors::{ErrorKind, NodeError, classify};
+use super::flow_routes::{FlowRoutes, is_truthy};
+use super::http_request;
+use super::journal::{
+    HostCall, HostCallLog, Journal, JournalEntry, JournalOutcome, ReplayDivergence,
+};
 use super::mocks::MockLayer;
-use crate::config::{HostConfig, McpRetryConfig};
-use crate::pack::{FlowDescriptor, PackRuntime};
+use super::nodes::{NodeContext, NodeRegistry};
+use super::state_nodes;
+use super::templating::{
+    HelperRegistry, Redactor, TemplateHelper, is_builtin_helper, register_builtin_helpers,
+    template_references,
+};
+use crate::config::{DEFAULT_MAX_NODE_VISITS, HostConfig, McpRetryConfig, SecretsPolicy};
+use crate::pack::{FlowDescriptor, FlowSchemas, PackFlow, PackRuntime};
+use crate::storage::state::{DynStateStore, new_state_store};
 #[cfg(feature = "mcp")]
 use crate::telemetry::tenant_context;
 use crate::telemetry::{FlowSpanAttributes, annotate_span, backoff_delay_ms, set_flow_context};
@@ -27,11 +48,31 @@ pub struct FlowEngine {
     packs: Vec<Arc<PackRuntime>>,
     flows: Vec<FlowDescriptor>,
     flow_sources: HashMap<String, usize>,
-    flow_ir: RwLock<HashMap<String, FlowIR>>,
+    flow_ir: RwLock<HashMap<String, PackFlow>>,
     #[cfg(feature = "mcp")]
     exec_config: ExecConfig,
     template_engine: Arc<Handlebars<'static>>,
     default_env: String,
+    /// Per-node retry overrides keyed by flow id, then node id.
+    node_retries: HashMap<String, HashMap<String, RetryConfig>>,
+    /// Embedder-supplied node components.
+    nodes: NodeRegistry,
+    /// Node components exported by packs, mapped to the pack that serves them.
+    pack_nodes: HashMap<String, usize>,
+    /// Backs the `state.*` nodes.
+    state_store: DynStateStore,
+    /// `http_enabled` from the bindings; gates `http.request` nodes.
+    http_enabled: bool,
+    http_client: reqwest::Client,
+    /// Gates `{{secrets.NAME}}` and `{{env.NAME}}` references in payload templates.
+    secrets_policy: SecretsPolicy,
+}
+
+/// Embedder-supplied additions to the engine.
+#[derive(Default, Clone)]
+pub struct EngineExtensions {
+    pub nodes: NodeRegistry,
+    pub helpers: HelperRegistry,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -39,6 +80,102 @@ pub struct FlowSnapshot {
     pub flow_id: String,
     pub next_node: String,
     pub state: ExecutionState,
+    /// Sub-flows paused beneath this flow, outermost first. When non-empty, `next_node` is
+    /// the `flow.call` node waiting for the first frame to complete.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub call_stack: Vec<CallFrame>,
+    /// Set when the waiting `session.wait` node asked for a timeout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<WaitTimeout>,
+}
+
+/// A paused sub-flow inside a [`FlowSnapshot`] call stack.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CallFrame {
+    pub flow_id: String,
+    pub next_node: String,
+    pub state: ExecutionState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<WaitTimeout>,
+}
+
+/// Expiry of a `session.wait` node that set `timeout_ms`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaitTimeout {
+    pub timeout_ms: u64,
+    /// Unix time in milliseconds after which the wait expires.
+    pub wake_at_ms: u64,
+    /// Node to resume at on expiry: the timeout route, or the regular route without one.
+    pub next_node: String,
+}
+
+impl FlowSnapshot {
+    /// Timeout of the innermost waiting flow, if it set one.
+    pub fn timeout(&self) -> Option<&WaitTimeout> {
+        match self.call_stack.last() {
+            Some(frame) => frame.timeout.as_ref(),
+            None => self.timeout.as_ref(),
+        }
+    }
+
+    /// Rename the nodes this snapshot points at, and their recorded outputs, using
+    /// `migrations` (flow id, then old node id to new node id).
+    pub fn migrate_nodes(&mut self, migrations: &HashMap<String, HashMap<String, String>>) {
+        let frames = std::iter::once((
+            &self.flow_id,
+            &mut self.next_node,
+            &mut self.timeout,
+            &mut self.state,
+        ))
+        .chain(self.call_stack.iter_mut().map(|frame| {
+            (
+                &frame.flow_id,
+                &mut frame.next_node,
+                &mut frame.timeout,
+                &mut frame.state,
+            )
+        }));
+        for (flow_id, next_node, timeout, state) in frames {
+            let Some(renames) = migrations.get(flow_id) else {
+                continue;
+            };
+            for (from, to) in renames {
+                if next_node == from {
+                    *next_node = to.clone();
+                }
+                if let Some(timeout) = timeout
+                    .as_mut()
+                    .filter(|timeout| &timeout.next_node == from)
+                {
+                    timeout.next_node = to.clone();
+                }
+                state.rename_node(from, to);
+            }
+        }
+    }
+
+    /// Flatten this snapshot and its call stack into frames, outermost first.
+    fn into_frames(self) -> Vec<CallFrame> {
+        let mut frames = Vec::with_capacity(self.call_stack.len() + 1);
+        frames.push(CallFrame {
+            flow_id: self.flow_id,
+            next_node: self.next_node,
+            state: self.state,
+            timeout: self.timeout,
+        });
+        frames.extend(self.call_stack);
+        frames
+    }
+}
+
+enum FlowEntry {
+    Start,
+    At(String),
+    /// Continue routing after `node_id`, whose output was produced elsewhere (a resumed sub-flow).
+    After {
+        node_id: String,
+        output: NodeOutput,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -47,6 +184,8 @@ pub struct FlowWait {
     pub snapshot: FlowSnapshot,
 }
 
+// Waiting is the cold path; boxing it would only add noise at every match site.
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug)]
 pub enum FlowStatus {
     Completed,
@@ -77,8 +216,18 @@ impl FlowExecution {
 
 impl FlowEngine {
     pub async fn new(packs: Vec<Arc<PackRuntime>>, config: Arc<HostConfig>) -> Result<Self> {
-        #[cfg(not(feature = "mcp"))]
-        let _ = &config;
+        Self::with_extensions(packs, config, EngineExtensions::default()).await
+    }
+
+    /// Build an engine with embedder-supplied node components and template helpers.
+    ///
+    /// Fails if any flow references a component that is neither built in nor registered.
+    pub async fn with_extensions(
+        packs: Vec<Arc<PackRuntime>>,
+        config: Arc<HostConfig>,
+        extensions: EngineExtensions,
+    ) -> Result<Self> {
+        let EngineExtensions { nodes, helpers } = extensions;
         let mut flow_sources = HashMap::new();
         let mut descriptors = Vec::new();
         for (idx, pack) in packs.iter().enumerate() {
@@ -101,14 +250,31 @@ impl FlowEngine {
             .mcp_exec_config()
             .context("failed to build MCP executor config")?;
 
+        let mut pack_nodes = HashMap::new();
+        for (idx, pack) in packs.iter().enumerate() {
+            for component in pack.node_components() {
+                if nodes.supports(component) {
+                    tracing::warn!(
+                        component = %component,
+                        pack_index = idx,
+                        "pack node component shadowed by a host component"
+                    );
+                }
+                pack_nodes.insert(component.clone(), idx);
+            }
+        }
+
         let mut ir_map = HashMap::new();
         for flow in &descriptors {
             if let Some(&pack_idx) = flow_sources.get(&flow.id) {
                 let pack_clone = Arc::clone(&packs[pack_idx]);
                 let flow_id = flow.id.clone();
                 let task_flow_id = flow_id.clone();
-                match task::spawn_blocking(move || pack_clone.load_flow_ir(&task_flow_id)).await {
+                match task::spawn_blocking(move || pack_clone.load_flow(&task_flow_id)).await {
                     Ok(Ok(ir)) => {
+                        check_components(&flow_id, &ir, |component| {
+                            nodes.supports(component) || pack_nodes.contains_key(component)
+                        })?;
                         ir_map.insert(flow_id, ir);
                     }
                     Ok(Err(err)) => {
@@ -121,8 +287,35 @@ impl FlowEngine {
             }
         }
 
+        let node_retries = config
+            .node_retries
+            .iter()
+            .map(|(flow_id, nodes)| {
+                let nodes = nodes
+                    .iter()
+                    .map(|(node_id, retry)| (node_id.clone(), RetryConfig::from(retry.clone())))
+                    .collect();
+                (flow_id.clone(), nodes)
+            })
+            .collect();
+
         let mut handlebars = Handlebars::new();
         handlebars.set_strict_mode(false);
+        register_builtin_helpers(&mut handlebars);
+        for (idx, pack) in packs.iter().enumerate() {
+            for (name, template) in pack.template_helpers() {
+                if is_builtin_helper(name) || helpers.contains(name) {
+                    tracing::warn!(
+                        helper = %name,
+                        pack_index = idx,
+                        "pack template helper shadowed by a host helper"
+                    );
+                    continue;
+                }
+                handlebars.register_helper(name, Box::new(TemplateHelper::new(name, template)));
+            }
+        }
+        helpers.install(&mut handlebars);
 
         Ok(Self {
             packs,
@@ -133,10 +326,29 @@ impl FlowEngine {
             exec_config,
             template_engine: Arc::new(handlebars),
             default_env: env::var("GREENTIC_ENV").unwrap_or_else(|_| "local".to_string()),
+            node_retries,
+            nodes,
+            pack_nodes,
+            state_store: new_state_store(),
+            http_enabled: config.http_enabled,
+            http_client: reqwest::Client::builder()
+                .build()
+                .context("failed to build http client")?,
+            secrets_policy: config.secrets_policy.clone(),
         })
     }
 
-    async fn get_or_load_flow_ir(&self, flow_id: &str) -> Result<FlowIR> {
+    /// Back the `state.*` nodes with `store` instead of a private in-memory store.
+    pub fn with_state_store(mut self, store: DynStateStore) -> Self {
+        self.state_store = store;
+        self
+    }
+
+    fn supports_component(&self, component: &str) -> bool {
+        self.nodes.supports(component) || self.pack_nodes.contains_key(component)
+    }
+
+    async fn get_or_load_flow_ir(&self, flow_id: &str) -> Result<PackFlow> {
         if let Some(ir) = self.flow_ir.read().get(flow_id).cloned() {
             return Ok(ir);
         }
@@ -148,9 +360,10 @@ impl FlowEngine {
         let pack = Arc::clone(&self.packs[pack_idx]);
         let flow_id_owned = flow_id.to_string();
         let task_flow_id = flow_id_owned.clone();
-        let ir = task::spawn_blocking(move || pack.load_flow_ir(&task_flow_id))
+        let ir = task::spawn_blocking(move || pack.load_flow(&task_flow_id))
             .await
             .context("failed to join flow metadata task")??;
+        check_components(flow_id, &ir, |component| self.supports_component(component))?;
         self.flow_ir
             .write()
             .insert(flow_id_owned.clone(), ir.clone());
@@ -184,35 +397,31 @@ impl FlowEngine {
             ctx.provider_id,
             ctx.session_id,
         );
-        let retry_config = ctx.retry_config;
-        let original_input = input;
-        async move {
-            let mut attempt = 0u32;
-            loop {
-                attempt += 1;
-                match self.execute_once(&ctx, original_input.clone()).await {
-                    Ok(value) => return Ok(value),
-                    Err(err) => {
-                        if attempt >= retry_config.max_attempts || !should_retry(&err) {
-                            return Err(err);
-                        }
-                        let delay = backoff_delay_ms(retry_config.base_delay_ms, attempt - 1);
-                        tracing::warn!(
-                            tenant = ctx.tenant,
-                            flow_id = ctx.flow_id,
-                            attempt,
-                            max_attempts = retry_config.max_attempts,
-                            delay_ms = delay,
-                            error = %err,
-                            "transient flow execution failure, backing off"
-                        );
-                        tokio::time::sleep(Duration::from_millis(delay)).await;
-                    }
-                }
+        async move { self.execute_once(&ctx, input).await }
+            .instrument(span)
+            .await
+    }
+
+    /// Whether every node `snapshot` would continue at exists in this engine's flows. Used
+    /// before resuming a snapshot saved by another version of the pack.
+    pub async fn can_resume(&self, snapshot: &FlowSnapshot) -> bool {
+        let frames = std::iter::once((&snapshot.flow_id, &snapshot.next_node, &snapshot.timeout))
+            .chain(
+                snapshot
+                    .call_stack
+                    .iter()
+                    .map(|frame| (&frame.flow_id, &frame.next_node, &frame.timeout)),
+            );
+        for (flow_id, next_node, timeout) in frames {
+            let Ok(flow) = self.get_or_load_flow_ir(flow_id).await else {
+                return false;
+            };
+            let exists = |node_id: &String| flow.ir.nodes.contains_key(node_id);
+            if !exists(next_node) || timeout.as_ref().is_some_and(|t| !exists(&t.next_node)) {
+                return false;
             }
         }
-        .instrument(span)
-        .await
+        true
     }
 
     pub async fn resume(
@@ -220,6 +429,30 @@ impl FlowEngine {
         ctx: FlowContext<'_>,
         snapshot: FlowSnapshot,
         input: Value,
+    ) -> Result<FlowExecution> {
+        self.resume_with(ctx, snapshot, input, false).await
+    }
+
+    /// Resume a flow whose `session.wait` timed out. The waiting node's timeout route is
+    /// followed with `{"timed_out": true, "timeout_ms": ..}` as input.
+    pub async fn resume_expired(
+        &self,
+        ctx: FlowContext<'_>,
+        snapshot: FlowSnapshot,
+    ) -> Result<FlowExecution> {
+        let timeout = snapshot
+            .timeout()
+            .with_context(|| format!("flow {} is not waiting with a timeout", snapshot.flow_id))?;
+        let input = json!({ "timed_out": true, "timeout_ms": timeout.timeout_ms });
+        self.resume_with(ctx, snapshot, input, true).await
+    }
+
+    async fn resume_with(
+        &self,
+        ctx: FlowContext<'_>,
+        snapshot: FlowSnapshot,
+        input: Value,
+        expired: bool,
     ) -> Result<FlowExecution> {
         if snapshot.flow_id != ctx.flow_id {
             bail!(
@@ -228,34 +461,134 @@ impl FlowEngine {
                 ctx.flow_id
             );
         }
-        let flow_ir = self.get_or_load_flow_ir(ctx.flow_id).await?;
-        let mut state = snapshot.state;
-        state.replace_input(input);
-        self.drive_flow(&ctx, flow_ir, state, Some(snapshot.next_node))
-            .await
+        let flow = self.get_or_load_flow_ir(ctx.flow_id).await?;
+        let FlowSnapshot {
+            next_node,
+            mut state,
+            mut call_stack,
+            timeout,
+            ..
+        } = snapshot;
+        state.replace_input(input.clone());
+        if call_stack.is_empty() {
+            let target = match timeout {
+                Some(timeout) if expired => timeout.next_node,
+                _ => next_node,
+            };
+            return self
+                .drive_flow(&ctx, flow, state, FlowEntry::At(target))
+                .await;
+        }
+
+        // Resume the innermost waiting sub-flow first, then hand its result back to the
+        // `flow.call` node that started it.
+        let frame = call_stack.remove(0);
+        let child_flow_id = frame.flow_id;
+        let child = FlowSnapshot {
+            flow_id: child_flow_id.clone(),
+            next_node: frame.next_node,
+            state: frame.state,
+            call_stack,
+            timeout: frame.timeout,
+        };
+        let execution =
+            Box::pin(self.resume_with(ctx.child(&child_flow_id), child, input, expired))
+                .await
+                .with_context(|| format!("flow.call failed for {child_flow_id}"))?;
+        match execution.status {
+            FlowStatus::Completed => {
+                let entry = FlowEntry::After {
+                    node_id: next_node,
+                    output: NodeOutput::new(execution.output),
+                };
+                self.drive_flow(&ctx, flow, state, entry).await
+            }
+            FlowStatus::Waiting(wait) => {
+                state.clear_egress();
+                let snapshot = FlowSnapshot {
+                    flow_id: ctx.flow_id.to_string(),
+                    next_node,
+                    state,
+                    call_stack: wait.snapshot.into_frames(),
+                    timeout: None,
+                };
+                Ok(FlowExecution::waiting(
+                    execution.output,
+                    FlowWait {
+                        reason: wait.reason,
+                        snapshot,
+                    },
+                ))
+            }
+        }
     }
 
     async fn execute_once(&self, ctx: &FlowContext<'_>, input: Value) -> Result<FlowExecution> {
         let flow_ir = self.get_or_load_flow_ir(ctx.flow_id).await?;
         let state = ExecutionState::new(input);
-        self.drive_flow(ctx, flow_ir, state, None).await
+        self.drive_flow(ctx, flow_ir, state, FlowEntry::Start).await
     }
 
     async fn drive_flow(
         &self,
         ctx: &FlowContext<'_>,
-        flow_ir: FlowIR,
-        mut state: ExecutionState,
-        resume_from: Option<String>,
+        flow: PackFlow,
+        state: ExecutionState,
+        entry: FlowEntry,
     ) -> Result<FlowExecution> {
-        let mut current = flow_ir
-            .start
-            .clone()
-            .or_else(|| flow_ir.nodes.keys().next().cloned())
-            .with_context(|| format!("flow {} has no start node", flow_ir.id))?;
-        if let Some(resume) = resume_from {
-            current = resume;
+        let event = FlowEvent {
+            context: ctx,
+            started_at: Instant::now(),
+        };
+        if let Some(observer) = ctx.observer {
+            match &entry {
+                FlowEntry::Start => observer.on_flow_start(&event),
+                FlowEntry::At(node_id) | FlowEntry::After { node_id, .. } => {
+                    observer.on_flow_resume(&event, node_id)
+                }
+            }
+        }
+        let result = self.walk_flow(ctx, flow, state, entry).await;
+        if let Some(observer) = ctx.observer {
+            match &result {
+                Ok(FlowExecution {
+                    output,
+                    status: FlowStatus::Completed,
+                }) => observer.on_flow_finish(&event, output),
+                Ok(FlowExecution {
+                    status: FlowStatus::Waiting(wait),
+                    ..
+                }) => observer.on_flow_wait(&event, wait),
+                Err(err) => observer.on_flow_error(&event, err.as_ref()),
+            }
         }
+        result
+    }
+
+    async fn walk_flow(
+        &self,
+        ctx: &FlowContext<'_>,
+        flow: PackFlow,
+        mut state: ExecutionState,
+        entry: FlowEntry,
+    ) -> Result<FlowExecution> {
+        let PackFlow {
+            ir: flow_ir,
+            routes,
+            ..
+        } = flow;
+        let (mut current, mut completed) = match entry {
+            FlowEntry::Start => {
+                let start = flow_ir
+                    .start
+                    .clone()
+                    .or_else(|| flow_ir.nodes.keys().next().cloned())
+                    .with_context(|| format!("flow {} has no start node", flow_ir.id))?;
+                (start, None)
+            }
+            FlowEntry::At(node_id) => (node_id, None),
+            FlowEntry::After { node_id, output } => (node_id, Some(output)),
+        };
         let mut final_payload = None;
 
         loop {
@@ -264,62 +597,105 @@ impl FlowEngine {
                 .get(&current)
                 .with_context(|| format!("node {current} not found"))?;
 
-            let context_value = state.context();
-            let payload = resolve_template_value(
-                self.template_engine.as_ref(),
-                &node.payload_expr,
-                &context_value,
-            )?;
-            let observed_payload = payload.clone();
-            let node_id = current.clone();
-            let event = NodeEvent {
-                context: ctx,
-                node_id: &node_id,
-                node,
-                payload: &observed_payload,
+            let (output, wait, failure) = match completed.take() {
+                Some(output) => (output, None, None),
+                None => {
+                    if let Some(debugger) = ctx.debugger {
+                        let stop = DebugStop {
+                            flow_id: ctx.flow_id,
+                            node_id: &current,
+                            node,
+                        };
+                        debugger.before_node(&stop, &mut state).await?;
+                    }
+                    match self
+                        .run_node(ctx, &current, node, &routes, &mut state)
+                        .await?
+                    {
+                        NodeRun::Finished {
+                            output,
+                            wait,
+                            failure,
+                        } => (output, wait, failure),
+                        NodeRun::ChildWaiting { output, wait } => {
+                            let mut snapshot_state = state.clone();
+                            snapshot_state.clear_egress();
+                            let snapshot = FlowSnapshot {
+                                flow_id: ctx.flow_id.to_string(),
+                                next_node: current,
+                                state: snapshot_state,
+                                call_stack: wait.snapshot.into_frames(),
+                                timeout: None,
+                            };
+                            return Ok(FlowExecution::waiting(
+                                state.finalize_with(Some(output)),
+                                FlowWait {
+                                    reason: wait.reason,
+                                    snapshot,
+                                },
+                            ));
+                        }
+                    }
+                }
             };
-            if let Some(observer) = ctx.observer {
-                observer.on_node_start(&event);
-            }
-            let DispatchOutcome {
-                output,
-                wait_reason,
-            } = self
-                .dispatch_node(ctx, &current, node, &mut state, payload)
-                .await?;
 
             state.nodes.insert(current.clone(), output.clone());
 
             let mut next = None;
             let mut should_exit = false;
-            for route in &node.routes {
-                if route.out || matches!(route.to.as_deref(), Some("out")) {
-                    final_payload = Some(output.payload.clone());
-                    should_exit = true;
-                    break;
+            let route_context = state.context();
+            let selected = routes.select(&current, failure.is_some(), |condition| {
+                self.evaluate_condition(condition, &route_context)
+                    .with_context(|| {
+                        format!("failed to evaluate route condition on node {current}")
+                    })
+            })?;
+            if let Some(err) = failure {
+                if selected.is_none() {
+                    return Err(err);
                 }
-                if let Some(to) = &route.to {
-                    next = Some(to.clone());
-                    break;
+                tracing::warn!(
+                    flow_id = ctx.flow_id,
+                    node_id = %current,
+                    error = %format!("{err:#}"),
+                    "node failed, following error route"
+                );
+            }
+            if let Some(route) = selected {
+                if route.is_exit() {
+                    final_payload = Some(final_output(&output));
+                    should_exit = true;
+                } else {
+                    next = route.to.clone();
                 }
             }
 
-            if let Some(wait_reason) = wait_reason {
+            if let Some(wait) = wait {
                 let resume_target = next.clone().ok_or_else(|| {
                     anyhow!("session.wait node {current} requires a non-empty route")
                 })?;
+                let timeout = wait.timeout_ms.map(|timeout_ms| WaitTimeout {
+                    timeout_ms,
+                    wake_at_ms: unix_millis().saturating_add(timeout_ms),
+                    next_node: routes
+                        .timeout_target(&current)
+                        .map(str::to_string)
+                        .unwrap_or_else(|| resume_target.clone()),
+                });
                 let mut snapshot_state = state.clone();
                 snapshot_state.clear_egress();
                 let snapshot = FlowSnapshot {
                     flow_id: ctx.flow_id.to_string(),
                     next_node: resume_target,
                     state: snapshot_state,
+                    call_stack: Vec::new(),
+                    timeout,
                 };
                 let output_value = state.clone().finalize_with(None);
                 return Ok(FlowExecution::waiting(
                     output_value,
                     FlowWait {
-                        reason: Some(wait_reason),
+                        reason: wait.reason,
                         snapshot,
                     },
                 ));
@@ -332,7 +708,7 @@ impl FlowEngine {
             match next {
                 Some(n) => current = n,
                 None => {
-                    final_payload = Some(output.payload.clone());
+                    final_payload = Some(final_output(&output));
                     break;
                 }
             }
@@ -342,10 +718,398 @@ impl FlowEngine {
         Ok(FlowExecution::completed(state.finalize_with(Some(payload))))
     }
 
+    /// Render, observe and dispatch a single node. Failures are returned as `failure` when the
+    /// node has an error route, and as `Err` otherwise.
+    async fn run_node(
+        &self,
+        ctx: &FlowContext<'_>,
+        current: &str,
+        node: &NodeIR,
+        routes: &FlowRoutes,
+        state: &mut ExecutionState,
+    ) -> Result<NodeRun> {
+        if ctx.cancel.is_cancelled() {
+            return Err(cancelled(ctx, current).into());
+        }
+        let visit = ctx
+            .node_budget
+            .consume()
+            .ok_or_else(|| NodeBudgetExceeded {
+                flow_id: ctx.flow_id.to_string(),
+                node_id: current.to_string(),
+                limit: ctx.node_budget.limit(),
+            })?;
+        let mut context_value = state.context();
+        if let (Some(token), Some(map)) = (ctx.resume_token, context_value.as_object_mut()) {
+            map.insert("resume_token".into(), Value::String(token.to_string()));
+        }
+        let mut redactor = Redactor::default();
+        let payload = self
+            .resolve_references(ctx, &node.payload_expr)
+            .and_then(|(secrets, env)| {
+                redactor = Redactor::new(
+                    secrets
+                        .values()
+                        .filter_map(|value| value.as_str().map(str::to_string)),
+                );
+                if let Some(map) = context_value.as_object_mut() {
+                    map.insert("secrets".into(), Value::Object(secrets));
+                    map.insert("env".into(), Value::Object(env));
+                }
+                resolve_template_value(
+                    self.template_engine.as_ref(),
+                    &node.payload_expr,
+                    &context_value,
+                )
+            })
+            .with_context(|| format!("failed to render payload for node {current}"));
+        // Emitted messages and sub-flow inputs end up in execution state, so they never
+        // carry resolved secrets.
+        let payload = if node.component.starts_with("emit") || is_composite(&node.component) {
+            payload.map(|payload| redactor.redact(payload))
+        } else {
+            payload
+        };
+        let observed_payload = redactor.redact(payload.as_ref().cloned().unwrap_or(Value::Null));
+        let event = NodeEvent {
+            context: ctx,
+            node_id: current,
+            node,
+            payload: &observed_payload,
+            visit,
+            started_at: Instant::now(),
+        };
+        if let Some(observer) = ctx.observer {
+            observer.on_node_start(&event);
+        }
+        let replay = ctx
+            .journal
+            .filter(|journal| journal.is_replay() && !is_composite(&node.component));
+        let dispatched = match (payload, replay) {
+            (Ok(payload), Some(journal)) => {
+                self.replay_node(
+                    ctx,
+                    journal,
+                    current,
+                    node,
+                    state,
+                    &observed_payload,
+                    payload,
+                )
+                .await
+            }
+            (Ok(payload), None) => {
+                self.dispatch_with_retry(ctx, current, node, state, payload)
+                    .await
+            }
+            (Err(err), _) => Err(err),
+        };
+        match dispatched {
+            Ok(DispatchOutcome {
+                output,
+                wait,
+                child_wait,
+                host_calls,
+            }) => {
+                let output = output.redacted(&redactor);
+                self.journal_node(
+                    ctx,
+                    current,
+                    node,
+                    &observed_payload,
+                    JournalOutcome::Ok {
+                        output: output.payload.clone(),
+                    },
+                    host_calls,
+                    &redactor,
+                );
+                if let Some(observer) = ctx.observer {
+                    observer.on_node_end(&event, &output.payload);
+                }
+                Ok(match child_wait {
+                    Some(wait) => NodeRun::ChildWaiting {
+                        output: output.payload,
+                        wait,
+                    },
+                    None => NodeRun::Finished {
+                        output,
+                        wait,
+                        failure: None,
+                    },
+                })
+            }
+            Err(err) => {
+                if let Some(observer) = ctx.observer {
+                    observer.on_node_error(&event, err.as_ref());
+                }
+                self.journal_node(
+                    ctx,
+                    current,
+                    node,
+                    &observed_payload,
+                    JournalOutcome::Failed {
+                        kind: classify(&err),
+                        message: format!("{err:#}"),
+                    },
+                    Vec::new(),
+                    &redactor,
+                );
+                let deadline = err.downcast_ref::<DeadlineExceeded>();
+                // An exhausted run budget, a cancelled run or a diverged replay cannot be
+                // recovered by routing elsewhere.
+                let run_expired = matches!(deadline, Some(DeadlineExceeded::Run { .. }));
+                let run_cancelled = err.downcast_ref::<FlowCancelled>().is_some();
+                let replay_diverged = err.downcast_ref::<ReplayDivergence>().is_some();
+                if run_expired
+                    || run_cancelled
+                    || replay_diverged
+                    || !routes.has_error_route(current)
+                {
+                    return Err(err);
+                }
+                Ok(NodeRun::Finished {
+                    output: NodeOutput::failed(&err).redacted(&redactor),
+                    wait: None,
+                    failure: Some(err),
+                })
+            }
+        }
+    }
+
+    /// Resolve the `secrets.NAME` and `env.NAME` references of a payload template. Names the
+    /// secrets policy does not allow fail the node as a policy error.
+    fn resolve_references(
+        &self,
+        ctx: &FlowContext<'_>,
+        template: &Value,
+    ) -> Result<(JsonMap<String, Value>, JsonMap<String, Value>)> {
+        let mut secrets = JsonMap::new();
+        for name in template_references(template, "secrets.") {
+            if !self.secrets_policy.is_allowed(&name) {
+                return Err(NodeError::new(
+                    ErrorKind::Policy,
+                    anyhow!("secret {name} is not permitted by bindings policy"),
+                )
+                .into());
+            }
+            // Journals only hold redacted payloads, so a replay never needs the real value.
+            let value = if ctx.journal.is_some_and(Journal::is_replay) {
+                Redactor::MASK.to_string()
+            } else {
+                ctx.mocks
+                    .and_then(|mocks| mocks.secrets_lookup(&name))
+                    .or_else(|| env::var(&name).ok())
+                    .ok_or_else(|| anyhow!("secret {name} not found"))?
+            };
+            secrets.insert(name, Value::String(value));
+        }
+        let mut vars = JsonMap::new();
+        for name in template_references(template, "env.") {
+            if !self.secrets_policy.is_allowed(&name) {
+                return Err(NodeError::new(
+                    ErrorKind::Policy,
+                    anyhow!("environment variable {name} is not permitted by bindings policy"),
+                )
+                .into());
+            }
+            let value =
+                env::var(&name).map_err(|_| anyhow!("environment variable {name} is not set"))?;
+            vars.insert(name, Value::String(value));
+        }
+        Ok((secrets, vars))
+    }
+
+    /// Answer a node from a replayed journal instead of dispatching it. Nodes that only touch
+    /// execution state still run, so the replay rebuilds the recorded state.
+    #[allow(clippy::too_many_arguments)]
+    async fn replay_node(
+        &self,
+        ctx: &FlowContext<'_>,
+        journal: &Journal,
+        node_id: &str,
+        node: &NodeIR,
+        state: &mut ExecutionState,
+        observed_payload: &Value,
+        payload: Value,
+    ) -> Result<DispatchOutcome> {
+        let entry = journal.replay(ctx.flow_id, node_id, observed_payload)?;
+        if replays_live(&node.component) {
+            return self.dispatch_node(ctx, node_id, node, state, payload).await;
+        }
+        match entry.outcome {
+            JournalOutcome::Ok { output } => Ok(DispatchOutcome::complete(NodeOutput::new(output))),
+            JournalOutcome::Failed { kind, message } => {
+                Err(NodeError::new(kind, anyhow!(message)).into())
+            }
+        }
+    }
+
+    /// Append a node's outcome to a recording journal. Composite nodes are left out; the
+    /// nodes they run are journaled on their own.
+    #[allow(clippy::too_many_arguments)]
+    fn journal_node(
+        &self,
+        ctx: &FlowContext<'_>,
+        node_id: &str,
+        node: &NodeIR,
+        observed_payload: &Value,
+        outcome: JournalOutcome,
+        host_calls: Vec<HostCall>,
+        redactor: &Redactor,
+    ) {
+        let Some(journal) = ctx.journal else {
+            return;
+        };
+        if is_composite(&node.component) || journal.is_replay() {
+            return;
+        }
+        let outcome = match outcome {
+            JournalOutcome::Failed { kind, message } => JournalOutcome::Failed {
+                kind,
+                message: match redactor.redact(Value::String(message)) {
+                    Value::String(message) => message,
+                    other => other.to_string(),
+                },
+            },
+            ok => ok,
+        };
+        journal.record(JournalEntry {
+            flow_id: ctx.flow_id.to_string(),
+            node_id: node_id.to_string(),
+            component: node.component.clone(),
+            payload: observed_payload.clone(),
+            outcome,
+            host_calls: host_calls
+                .into_iter()
+                .map(|call| HostCall {
+                    request: redactor.redact(call.request),
+                    response: redactor.redact(call.response),
+                    ..call
+                })
+                .collect(),
+        });
+    }
+
+    fn evaluate_condition(&self, condition: &Value, context: &Value) -> Result<bool> {
+        let rendered = resolve_template_value(self.template_engine.as_ref(), condition, context)?;
+        Ok(is_truthy(&rendered))
+    }
+
+    /// Dispatch a node, retrying transient failures according to the node's retry policy.
+    /// Composite nodes are attempted once; the nodes they run retry on their own.
+    async fn dispatch_with_retry(
+        &self,
+        ctx: &FlowContext<'_>,
+        node_id: &str,
+        node: &NodeIR,
+        state: &mut ExecutionState,
+        payload: Value,
+    ) -> Result<DispatchOutcome> {
+        let policy = if is_composite(&node.component) {
+            RetryConfig {
+                max_attempts: 1,
+                base_delay_ms: 0,
+            }
+        } else {
+            self.retry_policy(ctx, node_id)
+        };
+        let mut attempt = 0u32;
+        loop {
+            attempt += 1;
+            // Biased towards the dispatch so nodes running blocking work see the cancellation
+            // first and can interrupt it.
+            let dispatched = tokio::select! {
+                biased;
+                dispatched = self.dispatch_with_deadline(ctx, node_id, node, state, payload.clone()) => dispatched,
+                _ = ctx.cancel.cancelled() => Err(cancelled(ctx, node_id).into()),
+            };
+            let err = match dispatched {
+                Ok(outcome) => return Ok(outcome),
+                Err(err) => err,
+            };
+            let kind = classify(&err);
+            let Some(delay) = retry_delay(policy, attempt, kind, ctx.deadline) else {
+                return Err(err);
+            };
+            tracing::warn!(
+                tenant = ctx.tenant,
+                flow_id = ctx.flow_id,
+                node_id,
+                attempt,
+                max_attempts = policy.max_attempts,
+                delay_ms = delay.as_millis() as u64,
+                error = %err,
+                "transient node failure, backing off"
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = ctx.cancel.cancelled() => return Err(cancelled(ctx, node_id).into()),
+            }
+        }
+    }
+
+    fn retry_policy(&self, ctx: &FlowContext<'_>, node_id: &str) -> RetryConfig {
+        self.node_retries
+            .get(ctx.flow_id)
+            .and_then(|nodes| nodes.get(node_id))
+            .copied()
+            .unwrap_or(ctx.retry_config)
+    }
+
+    /// Dispatch a node bounded by the per-node timeout and the remaining run budget.
+    /// Composite nodes (`flow.call`, `flow.parallel`, `flow.map`) are only bounded by the
+    /// run deadline; the nodes they execute carry their own per-node limits.
+    async fn dispatch_with_deadline(
+        &self,
+        ctx: &FlowContext<'_>,
+        node_id: &str,
+        node: &NodeIR,
+        state: &mut ExecutionState,
+        payload: Value,
+    ) -> Result<DispatchOutcome> {
+        let node_limit = if is_composite(&node.component) {
+            None
+        } else {
+            ctx.node_timeout
+        };
+        let run_limit = ctx
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let run_exceeded = || DeadlineExceeded::Run {
+            node_id: node_id.to_string(),
+        };
+        let (limit, exceeded) = match (node_limit, run_limit) {
+            (None, None) => return self.dispatch_node(ctx, node_id, node, state, payload).await,
+            (Some(node_limit), Some(run_limit)) if run_limit < node_limit => {
+                (run_limit, run_exceeded())
+            }
+            (Some(node_limit), _) => (
+                node_limit,
+                DeadlineExceeded::Node {
+                    node_id: node_id.to_string(),
+                    timeout_ms: node_limit.as_millis() as u64,
+                },
+            ),
+            (None, Some(run_limit)) => (run_limit, run_exceeded()),
+        };
+        if limit.is_zero() {
+            return Err(exceeded.into());
+        }
+        match tokio::time::timeout(
+            limit,
+            self.dispatch_node(ctx, node_id, node, state, payload),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => Err(exceeded.into()),
+        }
+    }
+
     async fn dispatch_node(
         &self,
         ctx: &FlowContext<'_>,
-        _node_id: &str,
+        node_id: &str,
         node: &NodeIR,
         state: &mut ExecutionState,
         payload: Value,
@@ -353,29 +1117,140 @@ impl FlowEngine {
         match node.component.as_str() {
             "qa.process" => Ok(DispatchOutcome::complete(NodeOutput::new(payload))),
             "mcp.exec" => self
-                .execute_mcp(ctx, payload)
+                .execute_mcp(ctx, node_id, payload)
                 .await
                 .map(DispatchOutcome::complete),
             "templating.handlebars" => self
                 .execute_template(state, payload)
                 .map(DispatchOutcome::complete),
-            "flow.call" => self
-                .execute_flow_call(ctx, payload)
+            "flow.call" => self.execute_flow_call(ctx, payload).await,
+            "flow.parallel" => self
+                .execute_parallel(ctx, node_id, &node.payload_expr, state, payload)
+                .await
+                .map(DispatchOutcome::complete),
+            "flow.map" => self
+                .execute_map(ctx, node_id, state, payload)
                 .await
                 .map(DispatchOutcome::complete),
             component if component.starts_with("emit") => {
-                state.push_egress(payload.clone());
-                Ok(DispatchOutcome::complete(NodeOutput::new(payload)))
+                // A replay keeps its emits in the run output instead of sending them again.
+                let replaying = ctx.journal.is_some_and(Journal::is_replay);
+                let streamed = !replaying
+                    && ctx.egress.is_some_and(|egress| {
+                        egress.send(EgressItem {
+                            flow_id: ctx.flow_id.to_string(),
+                            node_id: node_id.to_string(),
+                            payload: payload.clone(),
+                        })
+                    });
+                if !streamed {
+                    state.push_egress(payload.clone());
+                }
+                Ok(DispatchOutcome::complete(
+                    NodeOutput::new(payload).delivered(),
+                ))
             }
+            "http.request" => http_request::execute(
+                self.http_enabled,
+                ctx.mocks,
+                &self.http_client,
+                node_id,
+                payload,
+            )
+            .await
+            .map(|output| DispatchOutcome::complete(NodeOutput::new(output))),
+            "state.get" | "state.set" | "state.delete" => state_nodes::execute(
+                self.state_store.as_ref(),
+                &self.default_env,
+                ctx,
+                &node.component,
+                node_id,
+                payload,
+            )
+            .map(|output| DispatchOutcome::complete(NodeOutput::new(output))),
             "session.wait" => {
-                let reason = extract_wait_reason(&payload);
-                Ok(DispatchOutcome::wait(NodeOutput::new(payload), reason))
+                let wait = extract_wait(&payload)
+                    .with_context(|| format!("invalid session.wait payload on node {node_id}"))?;
+                Ok(DispatchOutcome::wait(NodeOutput::new(payload), wait))
+            }
+            other => {
+                if let Some(handler) = self.nodes.get(other) {
+                    let context = state.context();
+                    let node_ctx = NodeContext {
+                        tenant: ctx.tenant,
+                        flow_id: ctx.flow_id,
+                        node_id,
+                        component: other,
+                        session_id: ctx.session_id,
+                        provider_id: ctx.provider_id,
+                        state: &context,
+                    };
+                    let output = handler.invoke(&node_ctx, payload).await?;
+                    return Ok(DispatchOutcome::complete(NodeOutput::new(output)));
+                }
+                self.execute_pack_node(ctx, node_id, other, payload).await
+            }
+        }
+    }
+
+    /// Run a node component implemented inside a pack via its `run-flow` export.
+    async fn execute_pack_node(
+        &self,
+        ctx: &FlowContext<'_>,
+        node_id: &str,
+        component: &str,
+        payload: Value,
+    ) -> Result<DispatchOutcome> {
+        let pack_idx = *self
+            .pack_nodes
+            .get(component)
+            .with_context(|| format!("unsupported node component: {component}"))?;
+        let pack = Arc::clone(&self.packs[pack_idx]);
+        let name = component.to_string();
+        let node_deadline = ctx.node_timeout.map(|limit| Instant::now() + limit);
+        let deadline = match (node_deadline, ctx.deadline) {
+            (Some(node), Some(run)) => Some(node.min(run)),
+            (node, run) => node.or(run),
+        };
+        let cancel = ctx.cancel.clone();
+        let host_calls = ctx.journal.map(|_| HostCallLog::default());
+        let log = host_calls.clone();
+        let worker = Arc::clone(&pack);
+        let call = task::spawn_blocking(move || {
+            worker.run_export(&name, &payload, deadline, Some(cancel), log)
+        });
+        // Once the deadline passes, tick the epoch so the Wasm call traps. The timer is not
+        // tied to this future, so it still fires after a deadline drops the dispatch.
+        let deadline_timer = deadline.map(|deadline| {
+            let pack = Arc::clone(&pack);
+            tokio::spawn(async move {
+                tokio::time::sleep_until(deadline.into()).await;
+                pack.interrupt();
+            })
+        });
+        let output = tokio::select! {
+            joined = call => joined.context("pack node task panicked"),
+            _ = ctx.cancel.cancelled() => {
+                // Trap the Wasm call at its next epoch check instead of leaving it running.
+                pack.interrupt();
+                return Err(cancelled(ctx, node_id).into());
             }
-            other => bail!("unsupported node component: {other}"),
+        };
+        if let Some(timer) = deadline_timer {
+            timer.abort();
         }
+        let output = output??;
+        let host_calls = host_calls
+            .map(|log| std::mem::take(&mut *log.lock()))
+            .unwrap_or_default();
+        Ok(DispatchOutcome::complete(NodeOutput::new(output)).with_host_calls(host_calls))
     }
 
-    async fn execute_flow_call(&self, ctx: &FlowContext<'_>, payload: Value) -> Result<NodeOutput> {
+    async fn execute_flow_call(
+        &self,
+        ctx: &FlowContext<'_>,
+        payload: Value,
+    ) -> Result<DispatchOutcome> {
         #[derive(Deserialize)]
         struct FlowCallPayload {
             #[serde(alias = "flow")]
@@ -397,45 +1272,217 @@ impl FlowEngine {
         };
 
         let flow_id_owned = call.flow_id;
-        let action = "flow.call";
-        let sub_ctx = FlowContext {
-            tenant: ctx.tenant,
-            flow_id: flow_id_owned.as_str(),
-            node_id: None,
-            tool: ctx.tool,
-            action: Some(action),
-            session_id: ctx.session_id,
-            provider_id: ctx.provider_id,
-            retry_config: ctx.retry_config,
-            observer: ctx.observer,
-            mocks: ctx.mocks,
-        };
-
-        let execution = Box::pin(self.execute(sub_ctx, sub_input))
+        let execution = Box::pin(self.execute(ctx.child(&flow_id_owned), sub_input))
             .await
             .with_context(|| format!("flow.call failed for {}", flow_id_owned))?;
+        let output = NodeOutput::new(execution.output);
         match execution.status {
-            FlowStatus::Completed => Ok(NodeOutput::new(execution.output)),
-            FlowStatus::Waiting(wait) => bail!(
-                "flow.call cannot pause (flow {} waiting {:?})",
-                flow_id_owned,
-                wait.reason
-            ),
+            FlowStatus::Completed => Ok(DispatchOutcome::complete(output)),
+            FlowStatus::Waiting(wait) => Ok(DispatchOutcome::child_waiting(output, wait)),
         }
     }
 
-    async fn execute_mcp(&self, ctx: &FlowContext<'_>, payload: Value) -> Result<NodeOutput> {
-        #[cfg(not(feature = "mcp"))]
-        {
-            let _ = (ctx, payload);
-            bail!("crate built without `mcp` feature; mcp.exec nodes are unavailable");
+    /// Run every branch concurrently and join their outputs. Inline component branches run
+    /// like regular nodes with the id `{node_id}.{label}`: they render their own payload
+    /// template, count toward the node budget, retry, stop in the debugger and are journaled.
+    async fn execute_parallel(
+        &self,
+        ctx: &FlowContext<'_>,
+        node_id: &str,
+        template: &Value,
+        state: &mut ExecutionState,
+        payload: Value,
+    ) -> Result<NodeOutput> {
+        let payload: ParallelPayload =
+            serde_json::from_value(payload).context("invalid payload for flow.parallel node")?;
+        let (labels, branches, named): (Vec<String>, Vec<ParallelBranch>, bool) =
+            match payload.branches {
+                ParallelBranches::Named(map) => {
+                    let (labels, branches) = map.into_iter().unzip();
+                    (labels, branches, true)
+                }
+                ParallelBranches::Listed(list) => {
+                    let labels = (0..list.len()).map(|idx| idx.to_string()).collect();
+                    (labels, list, false)
+                }
+            };
+        if branches.is_empty() {
+            bail!("flow.parallel node {node_id} requires at least one branch");
         }
 
-        #[cfg(feature = "mcp")]
-        {
-            #[derive(Deserialize)]
-            struct McpPayload {
-                component: String,
+        let mut base = state.clone();
+        base.clear_egress();
+        let routes = FlowRoutes::default();
+        let runs = branches
+            .into_iter()
+            .zip(&labels)
+            .enumerate()
+            .map(|(index, (branch, label))| {
+                let mut branch_state = base.clone();
+                let routes = &routes;
+                // The rendered payload had its secrets masked; inline branches render their
+                // own template instead.
+                let branch_template = branch_template(template, named, label, index);
+                async move {
+                    let sub_flow = branch.flow_id.is_some();
+                    let (component, branch_payload) = branch.into_dispatch(label)?;
+                    let branch_id = format!("{node_id}.{label}");
+                    let output = if sub_flow {
+                        let branch_node = NodeIR {
+                            component,
+                            payload_expr: Value::Null,
+                            routes: Vec::new(),
+                        };
+                        let outcome = Box::pin(self.dispatch_with_deadline(
+                            ctx,
+                            &branch_id,
+                            &branch_node,
+                            &mut branch_state,
+                            branch_payload,
+                        ))
+                        .await
+                        .with_context(|| format!("flow.parallel branch {label} failed"))?;
+                        if outcome.is_waiting() {
+                            bail!("flow.parallel branch {label} cannot pause");
+                        }
+                        outcome.output
+                    } else {
+                        let branch_node = NodeIR {
+                            component,
+                            payload_expr: branch_template.unwrap_or(branch_payload),
+                            routes: Vec::new(),
+                        };
+                        if let Some(debugger) = ctx.debugger {
+                            let stop = DebugStop {
+                                flow_id: ctx.flow_id,
+                                node_id: &branch_id,
+                                node: &branch_node,
+                            };
+                            debugger.before_node(&stop, &mut branch_state).await?;
+                        }
+                        let run = Box::pin(self.run_node(
+                            ctx,
+                            &branch_id,
+                            &branch_node,
+                            routes,
+                            &mut branch_state,
+                        ))
+                        .await
+                        .with_context(|| format!("flow.parallel branch {label} failed"))?;
+                        match run {
+                            NodeRun::Finished {
+                                output, wait: None, ..
+                            } => output,
+                            _ => bail!("flow.parallel branch {label} cannot pause"),
+                        }
+                    };
+                    Ok::<_, anyhow::Error>((output, branch_state.egress))
+                }
+            });
+        let results = try_join_all(runs).await?;
+
+        let delivered = results.iter().all(|(output, _)| output.delivered);
+        let mut joined = JsonMap::new();
+        let mut listed = Vec::with_capacity(results.len());
+        for (label, (output, egress)) in labels.into_iter().zip(results) {
+            for item in egress {
+                state.push_egress(item);
+            }
+            if named {
+                joined.insert(label, output.payload);
+            } else {
+                listed.push(output.payload);
+            }
+        }
+        let output = NodeOutput::new(if named {
+            Value::Object(joined)
+        } else {
+            Value::Array(listed)
+        });
+        Ok(if delivered {
+            output.delivered()
+        } else {
+            output
+        })
+    }
+
+    async fn execute_map(
+        &self,
+        ctx: &FlowContext<'_>,
+        node_id: &str,
+        state: &ExecutionState,
+        payload: Value,
+    ) -> Result<NodeOutput> {
+        let payload: MapPayload =
+            serde_json::from_value(payload).context("invalid payload for flow.map node")?;
+        if payload.flow_id.trim().is_empty() {
+            bail!("flow.map node {node_id} requires a non-empty flow_id");
+        }
+        let items = match payload.items {
+            Value::Array(items) => items,
+            Value::String(path) => match lookup_path(&state.context(), &path) {
+                Some(Value::Array(items)) => items.clone(),
+                Some(other) => bail!(
+                    "flow.map node {node_id}: `{path}` is not an array (found {})",
+                    value_kind(other)
+                ),
+                None => bail!("flow.map node {node_id}: `{path}` not found in context"),
+            },
+            Value::Null => Vec::new(),
+            other => bail!(
+                "flow.map node {node_id}: `items` must be an array or a context path (found {})",
+                value_kind(&other)
+            ),
+        };
+        let concurrency = payload
+            .concurrency
+            .unwrap_or(DEFAULT_MAP_CONCURRENCY)
+            .max(1);
+        let flow_id = payload.flow_id.as_str();
+        let shared_input = &payload.input;
+
+        let results = stream::iter(items.into_iter().enumerate())
+            .map(|(index, item)| async move {
+                let input = if shared_input.is_null() {
+                    item
+                } else {
+                    let mut input = shared_input.clone();
+                    merge_values(&mut input, json!({ "item": item, "index": index }));
+                    input
+                };
+                let outcome = self
+                    .execute_flow_call(ctx, json!({ "flow_id": flow_id, "input": input }))
+                    .await
+                    .with_context(|| format!("flow.map item {index} failed"))?;
+                if outcome.is_waiting() {
+                    bail!("flow.map item {index} cannot pause");
+                }
+                Ok(outcome.output.payload)
+            })
+            .buffered(concurrency)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(NodeOutput::new(Value::Array(results)))
+    }
+
+    async fn execute_mcp(
+        &self,
+        ctx: &FlowContext<'_>,
+        node_id: &str,
+        payload: Value,
+    ) -> Result<NodeOutput> {
+        #[cfg(not(feature = "mcp"))]
+        {
+            let _ = (ctx, node_id, payload);
+            bail!("crate built without `mcp` feature; mcp.exec nodes are unavailable");
+        }
+
+        #[cfg(feature = "mcp")]
+        {
+            #[derive(Deserialize)]
+            struct McpPayload {
+                component: String,
                 action: String,
                 #[serde(default)]
                 args: Value,
@@ -459,11 +1506,13 @@ impl FlowEngine {
             };
 
             let exec_config = self.exec_config.clone();
-            let exec_result =
-                task::spawn_blocking(move || greentic_mcp::exec(request, &exec_config))
-                    .await
-                    .context("failed to join mcp.exec")?;
-            let value = exec_result.map_err(|err| anyhow!(err))?;
+            let call = task::spawn_blocking(move || greentic_mcp::exec(request, &exec_config));
+            // The tool cannot be interrupted; a cancelled run stops waiting for it.
+            let exec_result = tokio::select! {
+                joined = call => joined.context("failed to join mcp.exec")?,
+                _ = ctx.cancel.cancelled() => return Err(cancelled(ctx, node_id).into()),
+            };
+            let value = exec_result.map_err(|err| NodeError::new(classify_exec(&err), err))?;
 
             Ok(NodeOutput::new(value))
         }
@@ -500,6 +1549,14 @@ impl FlowEngine {
             .find(|descriptor| descriptor.flow_type == flow_type)
     }
 
+    /// Input/output schemas declared by a loaded flow.
+    pub fn flow_schemas(&self, flow_id: &str) -> Option<FlowSchemas> {
+        self.flow_ir
+            .read()
+            .get(flow_id)
+            .map(|flow| flow.schemas.clone())
+    }
+
     pub fn flow_by_id(&self, flow_id: &str) -> Option<&FlowDescriptor> {
         self.flows
             .iter()
@@ -507,10 +1564,36 @@ impl FlowEngine {
     }
 }
 
+/// Hooks fired by the engine while a flow runs. Sub-flows started by `flow.call`,
+/// `flow.parallel` and `flow.map` report through the same observer.
 pub trait ExecutionObserver: Send + Sync {
     fn on_node_start(&self, event: &NodeEvent<'_>);
     fn on_node_end(&self, event: &NodeEvent<'_>, output: &Value);
-    fn on_node_error(&self, event: &NodeEvent<'_>, error: &dyn StdError);
+    fn on_node_error(&self, event: &NodeEvent<'_>, error: &(dyn StdError + 'static));
+
+    fn on_flow_start(&self, _event: &FlowEvent<'_>) {}
+    /// A paused flow picks up again at `next_node`.
+    fn on_flow_resume(&self, _event: &FlowEvent<'_>, _next_node: &str) {}
+    /// The flow paused on `session.wait`.
+    fn on_flow_wait(&self, _event: &FlowEvent<'_>, _wait: &FlowWait) {}
+    fn on_flow_finish(&self, _event: &FlowEvent<'_>, _output: &Value) {}
+    fn on_flow_error(&self, _event: &FlowEvent<'_>, _error: &(dyn StdError + 'static)) {}
+}
+
+/// Pauses a run before each node so it can be inspected and edited. Sub-flows started by
+/// `flow.call`, `flow.parallel` and `flow.map` stop in the same debugger.
+#[async_trait::async_trait]
+pub trait FlowDebugger: Send + Sync {
+    /// Called before `stop.node_id` runs; the run waits until this returns. Edits to `state`
+    /// are visible to the node's payload template. An error aborts the run.
+    async fn before_node(&self, stop: &DebugStop<'_>, state: &mut ExecutionState) -> Result<()>;
+}
+
+/// Where a run is about to stop.
+pub struct DebugStop<'a> {
+    pub flow_id: &'a str,
+    pub node_id: &'a str,
+    pub node: &'a NodeIR,
 }
 
 pub struct NodeEvent<'a> {
@@ -518,6 +1601,35 @@ pub struct NodeEvent<'a> {
     pub node_id: &'a str,
     pub node: &'a NodeIR,
     pub payload: &'a Value,
+    /// 1-based position of this node execution within the run, sub-flows included.
+    pub visit: u32,
+    pub started_at: Instant,
+}
+
+impl NodeEvent<'_> {
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+}
+
+pub struct FlowEvent<'a> {
+    pub context: &'a FlowContext<'a>,
+    pub started_at: Instant,
+}
+
+impl FlowEvent<'_> {
+    pub fn flow_id(&self) -> &str {
+        self.context.flow_id
+    }
+
+    /// Node executions charged to the run so far.
+    pub fn node_visits(&self) -> u32 {
+        self.context.node_budget.used()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -561,6 +1673,12 @@ impl ExecutionState {
         self.input = input;
     }
 
+    fn rename_node(&mut self, from: &str, to: &str) {
+        if let Some(output) = self.nodes.remove(from) {
+            self.nodes.insert(to.to_string(), output);
+        }
+    }
+
     fn clear_egress(&mut self) {
         self.egress.clear();
     }
@@ -586,6 +1704,9 @@ struct NodeOutput {
     ok: bool,
     payload: Value,
     meta: Value,
+    /// The payload already went out as egress, so a flow ending here does not repeat it.
+    #[serde(skip)]
+    delivered: bool,
 }
 
 impl NodeOutput {
@@ -594,29 +1715,181 @@ impl NodeOutput {
             ok: true,
             payload,
             meta: Value::Null,
+            delivered: false,
+        }
+    }
+
+    fn delivered(mut self) -> Self {
+        self.delivered = true;
+        self
+    }
+
+    fn redacted(self, redactor: &Redactor) -> Self {
+        Self {
+            ok: self.ok,
+            payload: redactor.redact(self.payload),
+            meta: redactor.redact(self.meta),
+            delivered: self.delivered,
+        }
+    }
+
+    fn failed(err: &anyhow::Error) -> Self {
+        let kind = classify(err);
+        Self {
+            ok: false,
+            delivered: false,
+            payload: Value::Null,
+            meta: json!({
+                "error": {
+                    "kind": kind,
+                    "message": format!("{err:#}"),
+                }
+            }),
         }
     }
 }
 
 struct DispatchOutcome {
     output: NodeOutput,
-    wait_reason: Option<String>,
+    wait: Option<WaitRequest>,
+    /// Set when a sub-flow started by this node paused.
+    child_wait: Option<FlowWait>,
+    /// Host imports a pack node made; kept for the journal.
+    host_calls: Vec<HostCall>,
 }
 
 impl DispatchOutcome {
     fn complete(output: NodeOutput) -> Self {
         Self {
             output,
-            wait_reason: None,
+            wait: None,
+            child_wait: None,
+            host_calls: Vec::new(),
+        }
+    }
+
+    fn wait(output: NodeOutput, wait: Option<WaitRequest>) -> Self {
+        Self {
+            output,
+            wait,
+            child_wait: None,
+            host_calls: Vec::new(),
         }
     }
 
-    fn wait(output: NodeOutput, reason: Option<String>) -> Self {
+    fn child_waiting(output: NodeOutput, wait: FlowWait) -> Self {
         Self {
             output,
-            wait_reason: reason,
+            wait: None,
+            child_wait: Some(wait),
+            host_calls: Vec::new(),
         }
     }
+
+    fn with_host_calls(mut self, host_calls: Vec<HostCall>) -> Self {
+        self.host_calls = host_calls;
+        self
+    }
+
+    fn is_waiting(&self) -> bool {
+        self.wait.is_some() || self.child_wait.is_some()
+    }
+}
+
+/// What a `session.wait` node asked for.
+struct WaitRequest {
+    reason: Option<String>,
+    timeout_ms: Option<u64>,
+}
+
+enum NodeRun {
+    Finished {
+        output: NodeOutput,
+        wait: Option<WaitRequest>,
+        failure: Option<anyhow::Error>,
+    },
+    ChildWaiting {
+        output: Value,
+        wait: FlowWait,
+    },
+}
+
+#[derive(Deserialize)]
+struct ParallelPayload {
+    branches: ParallelBranches,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ParallelBranches {
+    Named(IndexMap<String, ParallelBranch>),
+    Listed(Vec<ParallelBranch>),
+}
+
+/// A single `flow.parallel` branch: either an inline component call or a sub-flow.
+#[derive(Deserialize)]
+struct ParallelBranch {
+    #[serde(default)]
+    component: Option<String>,
+    #[serde(default)]
+    payload: Value,
+    #[serde(default, alias = "flow")]
+    flow_id: Option<String>,
+    #[serde(default)]
+    input: Value,
+}
+
+impl ParallelBranch {
+    fn into_dispatch(self, label: &str) -> Result<(String, Value)> {
+        match (self.component, self.flow_id) {
+            (Some(component), None) => Ok((component, self.payload)),
+            (None, Some(flow_id)) => Ok((
+                "flow.call".to_string(),
+                json!({ "flow_id": flow_id, "input": self.input }),
+            )),
+            (Some(_), Some(_)) => {
+                bail!("flow.parallel branch {label} sets both `component` and `flow`")
+            }
+            (None, None) => bail!("flow.parallel branch {label} requires `component` or `flow`"),
+        }
+    }
+}
+
+/// The unrendered `payload` of a parallel branch, when the node lists its branches literally.
+fn branch_template(template: &Value, named: bool, label: &str, index: usize) -> Option<Value> {
+    let branches = template.get("branches")?;
+    let branch = if named {
+        branches.get(label)?
+    } else {
+        branches.get(index)?
+    };
+    branch.get("payload").cloned()
+}
+
+/// What a flow that ends at a node returns: its payload, unless that already went out as
+/// egress.
+fn final_output(output: &NodeOutput) -> Value {
+    if output.delivered {
+        Value::Null
+    } else {
+        output.payload.clone()
+    }
+}
+
+const DEFAULT_MAP_CONCURRENCY: usize = 4;
+
+#[derive(Deserialize)]
+struct MapPayload {
+    /// Inline array, or a dotted path into the execution context (`nodes.search.payload.hits`).
+    #[serde(default)]
+    items: Value,
+    #[serde(alias = "flow")]
+    flow_id: String,
+    /// Shared sub-flow input; each item is merged in as `item`/`index` when present.
+    #[serde(default)]
+    input: Value,
+    #[serde(default)]
+    concurrency: Option<usize>,
 }
 
 #[derive(Deserialize)]
@@ -652,6 +1925,9 @@ fn resolve_template_value(
             Ok(Value::Array(values))
         }
         Value::Object(map) => {
+            if let Some(reference) = ValueRef::parse(map) {
+                return Ok(reference.resolve(context));
+            }
             let mut resolved = JsonMap::new();
             for (key, v) in map {
                 resolved.insert(key.clone(), resolve_template_value(engine, v, context)?);
@@ -662,6 +1938,44 @@ fn resolve_template_value(
     }
 }
 
+/// A typed reference into the template context: `{"$ref": "nodes.lookup.payload.items"}`
+/// yields the referenced JSON value as-is instead of rendering it to a string. The path is
+/// dotted (array indices allowed) or a JSON pointer when it starts with `/`; `default` is used
+/// when nothing is found.
+struct ValueRef<'v> {
+    path: &'v str,
+    default: Option<&'v Value>,
+}
+
+impl<'v> ValueRef<'v> {
+    /// Only objects made of `$ref` (plus an optional `default`) are references, so payloads
+    /// that merely carry a `$ref` key, such as JSON Schemas, pass through untouched. JSON
+    /// Schema style targets (`#/definitions/order`, `order.json#/id`,
+    /// `https://example.com/order.json`) are never context paths and stay as they are too.
+    fn parse(map: &'v JsonMap<String, Value>) -> Option<Self> {
+        let path = map.get("$ref")?.as_str()?;
+        if map.keys().any(|key| key != "$ref" && key != "default") {
+            return None;
+        }
+        if path.contains('#') || path.contains("://") {
+            return None;
+        }
+        Some(Self {
+            path,
+            default: map.get("default"),
+        })
+    }
+
+    fn resolve(&self, context: &Value) -> Value {
+        let found = if self.path.starts_with('/') {
+            context.pointer(self.path)
+        } else {
+            lookup_path(context, self.path)
+        };
+        found.or(self.default).cloned().unwrap_or(Value::Null)
+    }
+}
+
 fn merge_values(target: &mut Value, addition: Value) {
     match (target, addition) {
         (Value::Object(target_map), Value::Object(add_map)) => {
@@ -692,17 +2006,62 @@ fn render_template(
         .with_context(|| "failed to render template")
 }
 
-fn extract_wait_reason(payload: &Value) -> Option<String> {
-    match payload {
-        Value::String(s) => Some(s.clone()),
-        Value::Object(map) => map
-            .get("reason")
-            .and_then(Value::as_str)
-            .map(|value| value.to_string()),
-        _ => None,
+fn lookup_path<'v>(value: &'v Value, path: &str) -> Option<&'v Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |current, segment| match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|idx| items.get(idx)),
+            _ => None,
+        })
+}
+
+fn value_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
     }
 }
 
+/// A string payload is the wait reason; an object may carry `reason` and `timeout_ms`.
+/// Payloads with neither do not pause the flow.
+fn extract_wait(payload: &Value) -> Result<Option<WaitRequest>> {
+    let wait = match payload {
+        Value::String(s) => WaitRequest {
+            reason: Some(s.clone()),
+            timeout_ms: None,
+        },
+        Value::Object(map) => {
+            let timeout_ms = match map.get("timeout_ms") {
+                None | Some(Value::Null) => None,
+                Some(value) => Some(value.as_u64().with_context(|| {
+                    format!("timeout_ms must be a non-negative integer, got {value}")
+                })?),
+            };
+            WaitRequest {
+                reason: map
+                    .get("reason")
+                    .and_then(Value::as_str)
+                    .map(|value| value.to_string()),
+                timeout_ms,
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok((wait.reason.is_some() || wait.timeout_ms.is_some()).then_some(wait))
+}
+
+pub(crate) fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 #[cfg(feature = "mcp")]
 fn types_tenant_ctx(ctx: &FlowContext<'_>, default_env: &str) -> TypesTenantCtx {
     tenant_context(
@@ -715,10 +2074,1506 @@ fn types_tenant_ctx(ctx: &FlowContext<'_>, default_env: &str) -> TypesTenantCtx
     )
 }
 
+#[cfg(test)]
+impl FlowEngine {
+    /// Load `ir` as if a pack declared it, schemas included.
+    pub(crate) fn insert_test_flow(&mut self, ir: greentic_flow::ir::FlowIR) {
+        self.flows.push(FlowDescriptor {
+            id: ir.id.clone(),
+            flow_type: ir.flow_type.clone(),
+            profile: "default".into(),
+            version: "0.0.0".into(),
+            description: None,
+        });
+        self.flow_ir
+            .write()
+            .insert(ir.id.clone(), PackFlow::from_ir(ir));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{
+        FlowLimits, FlowTimeouts, JournalConfig, McpConfig, RateLimits, ResumePolicy,
+        SecretsPolicy, WebhookPolicy,
+    };
+    use crate::runner::errors::NodeError;
+    use greentic_flow::ir::{FlowIR, RouteIR};
     use serde_json::json;
+    use serde_yaml_bw as serde_yaml;
+
+    fn test_config() -> HostConfig {
+        HostConfig {
+            tenant: "acme".into(),
+            bindings_path: std::path::PathBuf::from("bindings.yaml"),
+            flow_type_bindings: HashMap::new(),
+            mcp: McpConfig {
+                store: serde_yaml::from_str("kind: local-dir\npath: ./tools").unwrap(),
+                security: serde_yaml::Value::default(),
+                runtime: serde_yaml::Value::default(),
+                http_enabled: Some(false),
+                retry: None,
+            },
+            rate_limits: RateLimits::default(),
+            http_enabled: false,
+            secrets_policy: SecretsPolicy::allow_all(),
+            webhook_policy: WebhookPolicy::default(),
+            timers: Vec::new(),
+            timeouts: FlowTimeouts::default(),
+            limits: FlowLimits::default(),
+            node_retries: HashMap::new(),
+            resume: ResumePolicy::default(),
+            journal: JournalConfig::default(),
+        }
+    }
+
+    async fn test_engine(flows: Vec<FlowIR>) -> FlowEngine {
+        let engine = FlowEngine::new(Vec::new(), Arc::new(test_config()))
+            .await
+            .unwrap();
+        for ir in flows {
+            engine
+                .flow_ir
+                .write()
+                .insert(ir.id.clone(), PackFlow::from_ir(ir));
+        }
+        engine
+    }
+
+    fn test_ctx(flow_id: &str) -> FlowContext<'_> {
+        FlowContext {
+            tenant: "acme",
+            flow_id,
+            node_id: None,
+            tool: None,
+            action: None,
+            session_id: None,
+            user_id: None,
+            provider_id: None,
+            retry_config: RetryConfig {
+                max_attempts: 1,
+                base_delay_ms: 0,
+            },
+            observer: None,
+            mocks: None,
+            deadline: None,
+            node_timeout: None,
+            node_budget: NodeBudget::default(),
+            resume_token: None,
+            egress: None,
+            cancel: CancellationToken::new(),
+            journal: None,
+            debugger: None,
+        }
+    }
+
+    fn node(component: &str, payload: Value, to: Option<&str>) -> NodeIR {
+        let routes = match to {
+            Some("out") => vec![RouteIR {
+                to: None,
+                out: true,
+            }],
+            Some(target) => vec![RouteIR {
+                to: Some(target.to_string()),
+                out: false,
+            }],
+            None => Vec::new(),
+        };
+        NodeIR {
+            component: component.to_string(),
+            payload_expr: payload,
+            routes,
+        }
+    }
+
+    fn flow(id: &str, nodes: Vec<(&str, NodeIR)>) -> FlowIR {
+        FlowIR {
+            id: id.to_string(),
+            flow_type: "messaging".into(),
+            start: nodes.first().map(|(id, _)| id.to_string()),
+            parameters: json!({}),
+            nodes: nodes
+                .into_iter()
+                .map(|(id, node)| (id.to_string(), node))
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn parallel_joins_named_branches() {
+        let engine = test_engine(vec![
+            flow(
+                "main",
+                vec![(
+                    "fanout",
+                    node(
+                        "flow.parallel",
+                        json!({
+                            "branches": {
+                                "weather": { "flow": "weather", "input": { "city": "{{input.city}}" } },
+                                "greeting": { "component": "qa.process", "payload": { "text": "hi" } }
+                            }
+                        }),
+                        Some("out"),
+                    ),
+                )],
+            ),
+            flow(
+                "weather",
+                vec![(
+                    "reply",
+                    node("qa.process", json!({ "forecast": "sunny in {{input.city}}" }), Some("out")),
+                )],
+            ),
+        ])
+        .await;
+
+        let execution = engine
+            .execute(test_ctx("main"), json!({ "city": "Paris" }))
+            .await
+            .unwrap();
+        assert_eq!(
+            execution.output,
+            json!({
+                "weather": { "forecast": "sunny in Paris" },
+                "greeting": { "text": "hi" }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn parallel_list_keeps_order_and_egress() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![(
+                "fanout",
+                node(
+                    "flow.parallel",
+                    json!({
+                        "branches": [
+                            { "component": "emit.text", "payload": { "text": "a" } },
+                            { "component": "emit.text", "payload": { "text": "b" } }
+                        ]
+                    }),
+                    None,
+                ),
+            )],
+        )])
+        .await;
+
+        let execution = engine.execute(test_ctx("main"), json!({})).await.unwrap();
+        // The join only repeats what the branches emitted, so it is not appended again.
+        assert_eq!(execution.output, json!([{ "text": "a" }, { "text": "b" }]));
+    }
+
+    #[tokio::test]
+    async fn map_collects_sub_flow_results_in_order() {
+        let engine = test_engine(vec![
+            flow(
+                "main",
+                vec![(
+                    "each",
+                    node(
+                        "flow.map",
+                        json!({
+                            "items": "input.cities",
+                            "flow": "greet",
+                            "input": { "greeting": "hello" },
+                            "concurrency": 2
+                        }),
+                        Some("out"),
+                    ),
+                )],
+            ),
+            flow(
+                "greet",
+                vec![(
+                    "reply",
+                    node(
+                        "qa.process",
+                        json!({ "text": "{{input.greeting}} {{input.item}} #{{input.index}}" }),
+                        Some("out"),
+                    ),
+                )],
+            ),
+        ])
+        .await;
+
+        let execution = engine
+            .execute(
+                test_ctx("main"),
+                json!({ "cities": ["Paris", "Oslo", "Lima"] }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            execution.output,
+            json!([
+                { "text": "hello Paris #0" },
+                { "text": "hello Oslo #1" },
+                { "text": "hello Lima #2" }
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn map_rejects_non_array_items() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![(
+                "each",
+                node(
+                    "flow.map",
+                    json!({ "items": "input.city", "flow": "x" }),
+                    None,
+                ),
+            )],
+        )])
+        .await;
+
+        let err = engine
+            .execute(test_ctx("main"), json!({ "city": "Paris" }))
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("is not an array"));
+    }
+
+    fn with_routes(ir: FlowIR, routing: Value) -> PackFlow {
+        let routes = FlowRoutes::from_document(&ir, &json!({ "nodes": routing }));
+        PackFlow {
+            routes,
+            ..PackFlow::from_ir(ir)
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_node_follows_error_route() {
+        let main = flow(
+            "main",
+            vec![
+                (
+                    "lookup",
+                    node("flow.call", json!({ "flow": "missing" }), Some("reply")),
+                ),
+                ("reply", node("qa.process", json!({ "text": "done" }), None)),
+                (
+                    "apology",
+                    node(
+                        "qa.process",
+                        json!({ "text": "sorry ({{nodes.lookup.meta.error.kind}})", "ok": "{{nodes.lookup.ok}}" }),
+                        None,
+                    ),
+                ),
+            ],
+        );
+        let engine = test_engine(Vec::new()).await;
+        engine.flow_ir.write().insert(
+            "main".into(),
+            with_routes(
+                main,
+                json!({
+                    "lookup": { "routing": [{ "to": "reply" }, { "to": "apology", "on_error": true }] }
+                }),
+            ),
+        );
+
+        let execution = engine.execute(test_ctx("main"), json!({})).await.unwrap();
+        assert_eq!(
+            execution.output,
+            json!({ "text": "sorry (permanent)", "ok": "false" })
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_wait_follows_timeout_route() {
+        let main = flow(
+            "main",
+            vec![
+                (
+                    "ask",
+                    node(
+                        "session.wait",
+                        json!({ "reason": "need-answer", "timeout_ms": 600000 }),
+                        Some("reply"),
+                    ),
+                ),
+                (
+                    "reply",
+                    node("qa.process", json!({ "text": "{{input.text}}" }), None),
+                ),
+                (
+                    "remind",
+                    node(
+                        "qa.process",
+                        json!({ "text": "still there? ({{input.timeout_ms}}ms)" }),
+                        None,
+                    ),
+                ),
+            ],
+        );
+        let engine = test_engine(Vec::new()).await;
+        engine.flow_ir.write().insert(
+            "main".into(),
+            with_routes(
+                main,
+                json!({
+                    "ask": { "routing": [{ "to": "reply" }, { "to": "remind", "on_timeout": true }] }
+                }),
+            ),
+        );
+
+        let before = unix_millis();
+        let first = engine.execute(test_ctx("main"), json!({})).await.unwrap();
+        let FlowStatus::Waiting(wait) = first.status else {
+            panic!("expected the flow to wait");
+        };
+        assert_eq!(wait.reason.as_deref(), Some("need-answer"));
+        let timeout = wait.snapshot.timeout().unwrap();
+        assert_eq!(timeout.next_node, "remind");
+        assert!(timeout.wake_at_ms >= before + 600000);
+
+        let answered = engine
+            .resume(
+                test_ctx("main"),
+                wait.snapshot.clone(),
+                json!({ "text": "yes" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(answered.output, json!({ "text": "yes" }));
+
+        let expired = engine
+            .resume_expired(test_ctx("main"), wait.snapshot)
+            .await
+            .unwrap();
+        assert_eq!(expired.output, json!({ "text": "still there? (600000ms)" }));
+    }
+
+    #[tokio::test]
+    async fn snapshots_migrate_onto_renamed_nodes() {
+        let old = test_engine(vec![flow(
+            "main",
+            vec![
+                (
+                    "lookup",
+                    node("qa.process", json!({ "text": "hi" }), Some("ask")),
+                ),
+                (
+                    "ask",
+                    node("session.wait", json!({ "reason": "name" }), Some("reply")),
+                ),
+                (
+                    "reply",
+                    node("qa.process", json!({ "text": "{{input.text}}" }), None),
+                ),
+            ],
+        )])
+        .await;
+        let first = old.execute(test_ctx("main"), json!({})).await.unwrap();
+        let FlowStatus::Waiting(wait) = first.status else {
+            panic!("expected the flow to wait");
+        };
+        let mut snapshot = wait.snapshot;
+
+        let new = test_engine(vec![flow(
+            "main",
+            vec![
+                (
+                    "fetch",
+                    node("qa.process", json!({ "text": "hi" }), Some("ask")),
+                ),
+                (
+                    "ask",
+                    node("session.wait", json!({ "reason": "name" }), Some("answer")),
+                ),
+                (
+                    "answer",
+                    node(
+                        "qa.process",
+                        json!({ "text": "{{nodes.fetch.payload.text}} {{input.text}}" }),
+                        None,
+                    ),
+                ),
+            ],
+        )])
+        .await;
+        assert!(!new.can_resume(&snapshot).await);
+
+        let migrations = HashMap::from([(
+            "main".to_string(),
+            HashMap::from([
+                ("lookup".to_string(), "fetch".to_string()),
+                ("reply".to_string(), "answer".to_string()),
+            ]),
+        )]);
+        snapshot.migrate_nodes(&migrations);
+        assert!(new.can_resume(&snapshot).await);
+        let resumed = new
+            .resume(test_ctx("main"), snapshot, json!({ "text": "Ada" }))
+            .await
+            .unwrap();
+        assert_eq!(resumed.output, json!({ "text": "hi Ada" }));
+    }
+
+    #[tokio::test]
+    async fn failure_without_error_route_aborts() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![(
+                "lookup",
+                node("flow.call", json!({ "flow": "missing" }), Some("out")),
+            )],
+        )])
+        .await;
+
+        let err = engine
+            .execute(test_ctx("main"), json!({}))
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("flow.call failed for missing"));
+    }
+
+    #[derive(Default)]
+    struct EventLog(parking_lot::Mutex<Vec<String>>);
+
+    impl EventLog {
+        fn push(&self, entry: String) {
+            self.0.lock().push(entry);
+        }
+
+        fn entries(&self) -> Vec<String> {
+            self.0.lock().clone()
+        }
+    }
+
+    impl ExecutionObserver for EventLog {
+        fn on_node_start(&self, event: &NodeEvent<'_>) {
+            self.push(format!("node.start {}", event.node_id));
+        }
+        fn on_node_end(&self, event: &NodeEvent<'_>, _output: &Value) {
+            self.push(format!("node.end {}", event.node_id));
+        }
+        fn on_node_error(&self, event: &NodeEvent<'_>, error: &(dyn StdError + 'static)) {
+            self.push(format!("node.error {}: {error}", event.node_id));
+        }
+        fn on_flow_start(&self, event: &FlowEvent<'_>) {
+            self.push(format!("flow.start {}", event.flow_id()));
+        }
+        fn on_flow_resume(&self, event: &FlowEvent<'_>, next_node: &str) {
+            self.push(format!("flow.resume {} at {next_node}", event.flow_id()));
+        }
+        fn on_flow_wait(&self, event: &FlowEvent<'_>, wait: &FlowWait) {
+            self.push(format!("flow.wait {} {:?}", event.flow_id(), wait.reason));
+        }
+        fn on_flow_finish(&self, event: &FlowEvent<'_>, _output: &Value) {
+            self.push(format!("flow.finish {}", event.flow_id()));
+        }
+        fn on_flow_error(&self, event: &FlowEvent<'_>, _error: &(dyn StdError + 'static)) {
+            self.push(format!("flow.error {}", event.flow_id()));
+        }
+    }
+
+    #[tokio::test]
+    async fn observer_sees_full_lifecycle_across_wait() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![
+                (
+                    "ask",
+                    node("session.wait", json!("need-input"), Some("reply")),
+                ),
+                (
+                    "reply",
+                    node("qa.process", json!({ "text": "{{input.text}}" }), None),
+                ),
+            ],
+        )])
+        .await;
+        let log = EventLog::default();
+        let mut ctx = test_ctx("main");
+        ctx.observer = Some(&log);
+
+        let first = engine.execute(ctx, json!({})).await.unwrap();
+        let FlowStatus::Waiting(wait) = first.status else {
+            panic!("expected the flow to wait");
+        };
+        let mut ctx = test_ctx("main");
+        ctx.observer = Some(&log);
+        let second = engine
+            .resume(ctx, wait.snapshot, json!({ "text": "hello" }))
+            .await
+            .unwrap();
+        assert_eq!(second.output, json!({ "text": "hello" }));
+        assert_eq!(
+            log.entries(),
+            [
+                "flow.start main",
+                "node.start ask",
+                "node.end ask",
+                "flow.wait main Some(\"need-input\")",
+                "flow.resume main at reply",
+                "node.start reply",
+                "node.end reply",
+                "flow.finish main",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn routing_loops_exhaust_the_node_budget() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![
+                ("ping", node("qa.process", json!({ "n": 1 }), Some("pong"))),
+                ("pong", node("qa.process", json!({ "n": 2 }), Some("ping"))),
+            ],
+        )])
+        .await;
+        let log = EventLog::default();
+        let mut ctx = test_ctx("main");
+        ctx.observer = Some(&log);
+        ctx.node_budget = NodeBudget::new(3);
+        let budget = ctx.node_budget.clone();
+
+        let err = engine.execute(ctx, json!({})).await.unwrap_err();
+        assert_eq!(classify(&err), ErrorKind::Policy);
+        let exceeded = err.downcast_ref::<NodeBudgetExceeded>().unwrap();
+        assert_eq!((exceeded.node_id.as_str(), exceeded.limit), ("pong", 3));
+        assert_eq!(budget.used(), 3);
+        assert_eq!(
+            log.entries()
+                .iter()
+                .filter(|entry| entry.starts_with("node.start"))
+                .count(),
+            3
+        );
+        assert_eq!(log.entries().last().unwrap(), "flow.error main");
+    }
+
+    #[tokio::test]
+    async fn payloads_can_reference_the_resume_token() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![(
+                "notify",
+                node(
+                    "qa.process",
+                    json!({ "link": "https://example.test/resume/{{resume_token}}" }),
+                    None,
+                ),
+            )],
+        )])
+        .await;
+        let mut ctx = test_ctx("main");
+        ctx.resume_token = Some("abc123");
+
+        let execution = engine.execute(ctx, json!({})).await.unwrap();
+        assert_eq!(
+            execution.output,
+            json!({ "link": "https://example.test/resume/abc123" })
+        );
+    }
+
+    #[tokio::test]
+    async fn emits_stream_to_the_egress_subscriber() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![
+                (
+                    "progress",
+                    node("emit.text", json!({ "text": "working" }), Some("done")),
+                ),
+                ("done", node("qa.process", json!({ "text": "done" }), None)),
+            ],
+        )])
+        .await;
+        let (egress, mut emitted) = egress_channel();
+        let mut ctx = test_ctx("main");
+        ctx.egress = Some(&egress);
+
+        let execution = engine.execute(ctx, json!({})).await.unwrap();
+        assert_eq!(execution.output, json!({ "text": "done" }));
+        let item = emitted.try_recv().unwrap();
+        assert_eq!(
+            (item.node_id.as_str(), item.payload),
+            ("progress", json!({ "text": "working" }))
+        );
+
+        // Without a listener the emit is buffered into the output again.
+        drop(emitted);
+        let mut ctx = test_ctx("main");
+        ctx.egress = Some(&egress);
+        let execution = engine.execute(ctx, json!({})).await.unwrap();
+        assert_eq!(
+            execution.output,
+            json!([{ "text": "working" }, { "text": "done" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn state_nodes_remember_values_per_scope() {
+        let engine = test_engine(vec![
+            flow(
+                "remember",
+                vec![(
+                    "save",
+                    node(
+                        "state.set",
+                        json!({ "key": "theme", "scope": "user", "value": "{{input.theme}}" }),
+                        None,
+                    ),
+                )],
+            ),
+            flow(
+                "recall",
+                vec![
+                    (
+                        "load",
+                        node(
+                            "state.get",
+                            json!({ "key": "theme", "scope": "user", "default": "light" }),
+                            Some("forget"),
+                        ),
+                    ),
+                    (
+                        "forget",
+                        node(
+                            "state.delete",
+                            json!({ "key": "theme", "scope": "user" }),
+                            Some("reply"),
+                        ),
+                    ),
+                    (
+                        "reply",
+                        node(
+                            "qa.process",
+                            json!({
+                                "theme": "{{nodes.load.payload.value}}",
+                                "deleted": "{{nodes.forget.payload.deleted}}"
+                            }),
+                            None,
+                        ),
+                    ),
+                ],
+            ),
+        ])
+        .await;
+        let user_ctx = |flow_id| {
+            let mut ctx = test_ctx(flow_id);
+            ctx.user_id = Some("ada");
+            ctx
+        };
+
+        engine
+            .execute(user_ctx("remember"), json!({ "theme": "dark" }))
+            .await
+            .unwrap();
+        let first = engine.execute(user_ctx("recall"), json!({})).await.unwrap();
+        assert_eq!(first.output, json!({ "theme": "dark", "deleted": "true" }));
+        let second = engine.execute(user_ctx("recall"), json!({})).await.unwrap();
+        assert_eq!(
+            second.output,
+            json!({ "theme": "light", "deleted": "false" })
+        );
+
+        // User scope needs a user, which the bare test context lacks.
+        let err = engine
+            .execute(test_ctx("recall"), json!({}))
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("uses user scope"));
+    }
+
+    #[tokio::test]
+    async fn http_request_replays_through_the_mock_layer() {
+        use crate::runner::mocks::{
+            HttpMock, HttpMockMode, HttpMockRequest, HttpMockResponse, MocksConfig,
+        };
+
+        let url = "https://api.example.test/lookup";
+        let cassettes = env::temp_dir().join(format!("greentic-http-node-{}", std::process::id()));
+        std::fs::create_dir_all(&cassettes).unwrap();
+        let request = HttpMockRequest::new("POST", url, Some(br#"{"q":"ada"}"#)).unwrap();
+        let recorded = HttpMockResponse::new(
+            200,
+            [("content-type".to_string(), "application/json".to_string())].into(),
+            Some(r#"{"name":"Ada"}"#.into()),
+        );
+        std::fs::write(
+            cassettes.join(format!("{}.json", request.fingerprint)),
+            serde_json::to_vec(&recorded).unwrap(),
+        )
+        .unwrap();
+        let mocks = MockLayer::new(
+            MocksConfig {
+                http: Some(HttpMock {
+                    record_replay_dir: Some(cassettes.clone()),
+                    mode: HttpMockMode::Replay,
+                    rewrites: Vec::new(),
+                }),
+                ..MocksConfig::default()
+            },
+            &cassettes,
+        )
+        .unwrap();
+
+        let mut engine = test_engine(vec![flow(
+            "main",
+            vec![(
+                "lookup",
+                node(
+                    "http.request",
+                    json!({ "method": "post", "url": url, "body": { "q": "{{input.q}}" } }),
+                    None,
+                ),
+            )],
+        )])
+        .await;
+        let mut ctx = test_ctx("main");
+        ctx.mocks = Some(&mocks);
+        let err = engine
+            .execute(ctx, json!({ "q": "ada" }))
+            .await
+            .unwrap_err();
+        assert_eq!(classify(&err), ErrorKind::Policy);
+
+        engine.http_enabled = true;
+        let mut ctx = test_ctx("main");
+        ctx.mocks = Some(&mocks);
+        let execution = engine.execute(ctx, json!({ "q": "ada" })).await.unwrap();
+        assert_eq!(execution.output["status"], json!(200));
+        assert_eq!(execution.output["body"], json!({ "name": "Ada" }));
+        std::fs::remove_dir_all(&cassettes).ok();
+    }
+
+    #[tokio::test]
+    async fn sub_flow_can_pause_and_return_to_parent() {
+        let engine = test_engine(vec![
+            flow(
+                "main",
+                vec![
+                    (
+                        "confirm",
+                        node(
+                            "flow.call",
+                            json!({ "flow": "confirm", "input": { "question": "Proceed?" } }),
+                            Some("done"),
+                        ),
+                    ),
+                    (
+                        "done",
+                        node(
+                            "qa.process",
+                            json!({ "answer": "{{nodes.confirm.payload.answer}}" }),
+                            None,
+                        ),
+                    ),
+                ],
+            ),
+            flow(
+                "confirm",
+                vec![
+                    (
+                        "ask",
+                        node(
+                            "emit.text",
+                            json!({ "text": "{{input.question}}" }),
+                            Some("wait"),
+                        ),
+                    ),
+                    ("wait", node("session.wait", json!("confirm"), Some("read"))),
+                    (
+                        "read",
+                        node("qa.process", json!({ "answer": "{{input.text}}" }), None),
+                    ),
+                ],
+            ),
+        ])
+        .await;
+
+        let first = engine.execute(test_ctx("main"), json!({})).await.unwrap();
+        assert_eq!(first.output, json!([{ "text": "Proceed?" }]));
+        let FlowStatus::Waiting(wait) = first.status else {
+            panic!("expected the flow to wait inside the sub-flow");
+        };
+        assert_eq!(wait.reason.as_deref(), Some("confirm"));
+        assert_eq!(wait.snapshot.flow_id, "main");
+        assert_eq!(wait.snapshot.next_node, "confirm");
+        assert_eq!(wait.snapshot.call_stack.len(), 1);
+        assert_eq!(wait.snapshot.call_stack[0].flow_id, "confirm");
+        assert_eq!(wait.snapshot.call_stack[0].next_node, "read");
+
+        // The snapshot survives a serialization round trip, as it does in the session store.
+        let stored: FlowSnapshot =
+            serde_json::from_value(serde_json::to_value(&wait.snapshot).unwrap()).unwrap();
+        let second = engine
+            .resume(test_ctx("main"), stored, json!({ "text": "yes" }))
+            .await
+            .unwrap();
+        assert!(matches!(second.status, FlowStatus::Completed));
+        assert_eq!(second.output, json!({ "answer": "yes" }));
+    }
+
+    #[tokio::test]
+    async fn expired_run_deadline_is_reported_as_timeout() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![("reply", node("qa.process", json!({ "text": "hi" }), None))],
+        )])
+        .await;
+        let log = EventLog::default();
+        let mut ctx = test_ctx("main");
+        ctx.deadline = Some(Instant::now());
+        ctx.observer = Some(&log);
+
+        let err = engine.execute(ctx, json!({})).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DeadlineExceeded>(),
+            Some(DeadlineExceeded::Run { node_id }) if node_id == "reply"
+        ));
+        assert_eq!(
+            log.entries(),
+            [
+                "flow.start main",
+                "node.start reply",
+                "node.error reply: run deadline exceeded while executing node reply",
+                "flow.error main",
+            ]
+        );
+    }
+
+    #[test]
+    fn retry_delay_only_backs_off_transient_failures() {
+        let policy = RetryConfig {
+            max_attempts: 3,
+            base_delay_ms: 10,
+        };
+        assert!(retry_delay(policy, 1, ErrorKind::Transient, None).is_some());
+        assert!(retry_delay(policy, 3, ErrorKind::Transient, None).is_none());
+        assert!(retry_delay(policy, 1, ErrorKind::Permanent, None).is_none());
+        assert!(retry_delay(policy, 1, ErrorKind::Timeout, None).is_none());
+        assert!(retry_delay(policy, 1, ErrorKind::Transient, Some(Instant::now())).is_none());
+    }
+
+    #[test]
+    fn failed_output_records_error_kind() {
+        let err = anyhow::Error::new(NodeError::new(
+            ErrorKind::Policy,
+            anyhow!("artifact is unsigned"),
+        ));
+        let output = NodeOutput::failed(&err);
+        assert!(!output.ok);
+        assert_eq!(output.meta["error"]["kind"], json!("policy"));
+        assert_eq!(
+            output.meta["error"]["message"],
+            json!("artifact is unsigned")
+        );
+    }
+
+    #[test]
+    fn lookup_path_walks_objects_and_arrays() {
+        let value = json!({ "nodes": { "search": { "payload": { "hits": [{ "id": 7 }] } } } });
+        assert_eq!(
+            lookup_path(&value, "nodes.search.payload.hits.0.id"),
+            Some(&json!(7))
+        );
+        assert_eq!(lookup_path(&value, "nodes.missing"), None);
+    }
+
+    #[tokio::test]
+    async fn parallel_branch_cannot_pause() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![(
+                "fanout",
+                node(
+                    "flow.parallel",
+                    json!({ "branches": [{ "component": "session.wait", "payload": "later" }] }),
+                    None,
+                ),
+            )],
+        )])
+        .await;
+
+        let err = engine
+            .execute(test_ctx("main"), json!({}))
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("cannot pause"));
+    }
+
+    struct CrmLookup;
+
+    #[async_trait::async_trait]
+    impl crate::runner::nodes::NodeHandler for CrmLookup {
+        async fn invoke(&self, ctx: &NodeContext<'_>, payload: Value) -> Result<Value> {
+            Ok(json!({
+                "node": ctx.node_id,
+                "customer": payload["email"],
+                "greeting": ctx.state["input"]["text"],
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn registered_handler_runs_custom_component() {
+        let mut nodes = NodeRegistry::default();
+        nodes.register("crm.lookup", Box::new(CrmLookup));
+        let extensions = EngineExtensions {
+            nodes,
+            ..Default::default()
+        };
+        let engine = FlowEngine::with_extensions(Vec::new(), Arc::new(test_config()), extensions)
+            .await
+            .unwrap();
+        let ir = flow(
+            "main",
+            vec![(
+                "lookup",
+                node(
+                    "crm.lookup",
+                    json!({ "email": "{{input.email}}" }),
+                    Some("out"),
+                ),
+            )],
+        );
+        check_components("main", &PackFlow::from_ir(ir.clone()), |component| {
+            engine.supports_component(component)
+        })
+        .unwrap();
+        engine
+            .flow_ir
+            .write()
+            .insert(ir.id.clone(), PackFlow::from_ir(ir));
+
+        let execution = engine
+            .execute(
+                test_ctx("main"),
+                json!({ "email": "ada@example.com", "text": "hello" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            execution.output,
+            json!({ "node": "lookup", "customer": "ada@example.com", "greeting": "hello" })
+        );
+    }
+
+    struct AuthCheck;
+
+    #[async_trait::async_trait]
+    impl crate::runner::nodes::NodeHandler for AuthCheck {
+        async fn invoke(&self, _ctx: &NodeContext<'_>, payload: Value) -> Result<Value> {
+            Ok(json!({
+                "authorized": payload["header"] == "Bearer tok-123",
+                "echo": payload["header"],
+                "pkg": payload["pkg"],
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn secret_references_resolve_but_never_reach_state() {
+        use crate::runner::mocks::{MocksConfig, SecretsMock};
+
+        let mut nodes = NodeRegistry::default();
+        nodes.register("auth.check", Box::new(AuthCheck));
+        let extensions = EngineExtensions {
+            nodes,
+            ..Default::default()
+        };
+        let mut engine =
+            FlowEngine::with_extensions(Vec::new(), Arc::new(test_config()), extensions)
+                .await
+                .unwrap();
+        let ir = flow(
+            "main",
+            vec![
+                (
+                    "check",
+                    node(
+                        "auth.check",
+                        json!({
+                            "header": "Bearer {{secrets.API_TOKEN}}",
+                            "pkg": "{{env.CARGO_PKG_NAME}}"
+                        }),
+                        Some("notify"),
+                    ),
+                ),
+                (
+                    "notify",
+                    node(
+                        "emit.text",
+                        json!({ "text": "used {{secrets.API_TOKEN}}" }),
+                        Some("done"),
+                    ),
+                ),
+                (
+                    "done",
+                    node(
+                        "qa.process",
+                        json!({
+                            "ok": "{{nodes.check.payload.authorized}}",
+                            "echo": "{{nodes.check.payload.echo}}",
+                            "pkg": "{{nodes.check.payload.pkg}}"
+                        }),
+                        None,
+                    ),
+                ),
+            ],
+        );
+        engine
+            .flow_ir
+            .write()
+            .insert(ir.id.clone(), PackFlow::from_ir(ir));
+        let mocks = MockLayer::new(
+            MocksConfig {
+                secrets: Some(SecretsMock {
+                    map: [("API_TOKEN".to_string(), "tok-123".to_string())].into(),
+                }),
+                ..MocksConfig::default()
+            },
+            &env::temp_dir(),
+        )
+        .unwrap();
+
+        let mut ctx = test_ctx("main");
+        ctx.mocks = Some(&mocks);
+        let execution = engine.execute(ctx, json!({})).await.unwrap();
+        assert_eq!(
+            execution.output,
+            json!([
+                { "text": "used [REDACTED]" },
+                { "ok": "true", "echo": "Bearer [REDACTED]", "pkg": "greentic-runner-host" }
+            ])
+        );
+
+        engine.secrets_policy = SecretsPolicy::from_allowed(["CARGO_PKG_NAME"]);
+        let mut ctx = test_ctx("main");
+        ctx.mocks = Some(&mocks);
+        let err = engine.execute(ctx, json!({})).await.unwrap_err();
+        assert_eq!(classify(&err), ErrorKind::Policy);
+    }
+
+    struct Stall;
+
+    #[async_trait::async_trait]
+    impl crate::runner::nodes::NodeHandler for Stall {
+        async fn invoke(&self, _ctx: &NodeContext<'_>, _payload: Value) -> Result<Value> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(Value::Null)
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_the_token_stops_the_run_mid_node() {
+        let mut nodes = NodeRegistry::default();
+        nodes.register("slow.call", Box::new(Stall));
+        let extensions = EngineExtensions {
+            nodes,
+            ..Default::default()
+        };
+        let engine = FlowEngine::with_extensions(Vec::new(), Arc::new(test_config()), extensions)
+            .await
+            .unwrap();
+        let ir = flow(
+            "main",
+            vec![
+                ("stall", node("slow.call", json!({}), Some("reply"))),
+                ("reply", node("emit.response", json!({}), Some("out"))),
+            ],
+        );
+        engine
+            .flow_ir
+            .write()
+            .insert(ir.id.clone(), PackFlow::from_ir(ir));
+
+        let ctx = test_ctx("main");
+        let cancel = ctx.cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            cancel.cancel();
+        });
+        let started = Instant::now();
+        let err = engine.execute(ctx, json!({})).await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(classify(&err), ErrorKind::Cancelled);
+        assert!(matches!(
+            err.downcast_ref::<FlowCancelled>(),
+            Some(FlowCancelled { node_id, .. }) if node_id == "stall"
+        ));
+    }
+
+    struct Counter(Arc<AtomicU32>);
+
+    #[async_trait::async_trait]
+    impl crate::runner::nodes::NodeHandler for Counter {
+        async fn invoke(&self, _ctx: &NodeContext<'_>, payload: Value) -> Result<Value> {
+            let calls = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(json!({ "calls": calls, "q": payload["q"] }))
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_reproduces_a_run_without_dispatching() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let mut nodes = NodeRegistry::default();
+        nodes.register("crm.count", Box::new(Counter(Arc::clone(&calls))));
+        let extensions = EngineExtensions {
+            nodes,
+            ..Default::default()
+        };
+        let engine = FlowEngine::with_extensions(Vec::new(), Arc::new(test_config()), extensions)
+            .await
+            .unwrap();
+        let ir = flow(
+            "main",
+            vec![
+                (
+                    "count",
+                    node("crm.count", json!({ "q": "{{input.q}}" }), Some("reply")),
+                ),
+                (
+                    "reply",
+                    node(
+                        "emit.response",
+                        json!({ "text": "{{nodes.count.payload.q}} #{{nodes.count.payload.calls}}" }),
+                        Some("out"),
+                    ),
+                ),
+            ],
+        );
+        engine
+            .flow_ir
+            .write()
+            .insert(ir.id.clone(), PackFlow::from_ir(ir));
+
+        let journal = Journal::recording();
+        let mut ctx = test_ctx("main");
+        ctx.journal = Some(&journal);
+        let recorded = engine.execute(ctx, json!({ "q": "ada" })).await.unwrap();
+        let entries = journal.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].payload, json!({ "q": "ada" }));
+
+        let replay = Journal::replaying(entries.clone());
+        let (egress, mut emitted) = egress_channel();
+        let mut ctx = test_ctx("main");
+        ctx.journal = Some(&replay);
+        ctx.egress = Some(&egress);
+        let replayed = engine.execute(ctx, json!({ "q": "ada" })).await.unwrap();
+        assert_eq!(replayed.output, recorded.output);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(replay.entries().is_empty());
+        drop(egress);
+        assert!(
+            emitted.recv().await.is_none(),
+            "replay must not re-send egress"
+        );
+
+        let replay = Journal::replaying(entries);
+        let mut ctx = test_ctx("main");
+        ctx.journal = Some(&replay);
+        let err = engine
+            .execute(ctx, json!({ "q": "grace" }))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<ReplayDivergence>().is_some());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    /// Fails its first call with a transient error, then counts like [`Counter`].
+    struct Flaky(Arc<AtomicU32>);
+
+    #[async_trait::async_trait]
+    impl crate::runner::nodes::NodeHandler for Flaky {
+        async fn invoke(&self, _ctx: &NodeContext<'_>, payload: Value) -> Result<Value> {
+            let calls = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            if calls == 1 {
+                return Err(NodeError::new(ErrorKind::Transient, anyhow!("flaky")).into());
+            }
+            Ok(json!({ "calls": calls, "q": payload["q"] }))
+        }
+    }
+
+    async fn inline_parallel_engine(calls: &Arc<AtomicU32>) -> FlowEngine {
+        let mut nodes = NodeRegistry::default();
+        nodes.register("crm.flaky", Box::new(Flaky(Arc::clone(calls))));
+        nodes.register("auth.check", Box::new(AuthCheck));
+        let extensions = EngineExtensions {
+            nodes,
+            ..Default::default()
+        };
+        let engine = FlowEngine::with_extensions(Vec::new(), Arc::new(test_config()), extensions)
+            .await
+            .unwrap();
+        let ir = flow(
+            "main",
+            vec![(
+                "fanout",
+                node(
+                    "flow.parallel",
+                    json!({
+                        "branches": {
+                            "lookup": { "component": "crm.flaky", "payload": { "q": "{{input.q}}" } },
+                            "auth": {
+                                "component": "auth.check",
+                                "payload": { "header": "Bearer {{secrets.API_TOKEN}}" }
+                            }
+                        }
+                    }),
+                    None,
+                ),
+            )],
+        );
+        engine
+            .flow_ir
+            .write()
+            .insert(ir.id.clone(), PackFlow::from_ir(ir));
+        engine
+    }
+
+    fn token_mocks() -> MockLayer {
+        use crate::runner::mocks::{MocksConfig, SecretsMock};
+
+        MockLayer::new(
+            MocksConfig {
+                secrets: Some(SecretsMock {
+                    map: [("API_TOKEN".to_string(), "tok-123".to_string())].into(),
+                }),
+                ..MocksConfig::default()
+            },
+            &env::temp_dir(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn inline_parallel_branches_run_as_nodes() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let engine = inline_parallel_engine(&calls).await;
+        let mocks = token_mocks();
+
+        let journal = Journal::recording();
+        let mut ctx = test_ctx("main");
+        ctx.retry_config = RetryConfig {
+            max_attempts: 2,
+            base_delay_ms: 0,
+        };
+        ctx.mocks = Some(&mocks);
+        ctx.journal = Some(&journal);
+        let budget = ctx.node_budget.clone();
+        let recorded = engine.execute(ctx, json!({ "q": "ada" })).await.unwrap();
+        // The flaky branch is retried, and the secret reaches the branch unmasked.
+        assert_eq!(recorded.output["lookup"], json!({ "calls": 2, "q": "ada" }));
+        assert_eq!(recorded.output["auth"]["authorized"], json!(true));
+        assert_eq!(recorded.output["auth"]["echo"], json!("Bearer [REDACTED]"));
+        assert_eq!(budget.used(), 3);
+        let mut journaled = journal
+            .entries()
+            .into_iter()
+            .map(|entry| entry.node_id)
+            .collect::<Vec<_>>();
+        journaled.sort();
+        assert_eq!(journaled, ["fanout.auth", "fanout.lookup"]);
+
+        // A replay answers the branches from the journal instead of calling them again.
+        let replay = Journal::replaying(journal.entries());
+        let mut ctx = test_ctx("main");
+        ctx.journal = Some(&replay);
+        let replayed = engine.execute(ctx, json!({ "q": "ada" })).await.unwrap();
+        assert_eq!(replayed.output, recorded.output);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(replay.entries().is_empty());
+    }
+
+    #[tokio::test]
+    async fn inline_parallel_branches_count_toward_the_node_budget() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let engine = inline_parallel_engine(&calls).await;
+        let mocks = token_mocks();
+        let mut ctx = test_ctx("main");
+        ctx.mocks = Some(&mocks);
+        ctx.node_budget = NodeBudget::new(2);
+        let err = engine
+            .execute(ctx, json!({ "q": "ada" }))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<NodeBudgetExceeded>().is_some());
+    }
+
+    /// Stops before every node; rewrites the greeting before `reply` and aborts at `abort`,
+    /// keeping the egress it saw there.
+    #[derive(Default)]
+    struct EditingDebugger {
+        stops: parking_lot::Mutex<Vec<String>>,
+        egress: parking_lot::Mutex<Value>,
+    }
+
+    #[async_trait::async_trait]
+    impl FlowDebugger for EditingDebugger {
+        async fn before_node(
+            &self,
+            stop: &DebugStop<'_>,
+            state: &mut ExecutionState,
+        ) -> Result<()> {
+            self.stops.lock().push(stop.node_id.to_string());
+            match stop.node_id {
+                "reply" => {
+                    let mut edited = serde_json::to_value(&*state)?;
+                    edited["nodes"]["greet"]["payload"]["text"] = json!("edited");
+                    *state = serde_json::from_value(edited)?;
+                    Ok(())
+                }
+                "abort" => {
+                    *self.egress.lock() = serde_json::to_value(&*state)?["egress"].clone();
+                    bail!("stopped by debugger")
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn debugger_pauses_before_nodes_and_edits_state() {
+        let engine = test_engine(vec![flow(
+            "main",
+            vec![
+                (
+                    "greet",
+                    node("qa.process", json!({ "text": "hello" }), Some("reply")),
+                ),
+                (
+                    "reply",
+                    node(
+                        "emit.response",
+                        json!({ "text": "{{nodes.greet.payload.text}}" }),
+                        Some("abort"),
+                    ),
+                ),
+                ("abort", node("qa.process", json!({}), Some("out"))),
+            ],
+        )])
+        .await;
+
+        let debugger = EditingDebugger::default();
+        let mut ctx = test_ctx("main");
+        ctx.debugger = Some(&debugger);
+        let err = engine.execute(ctx, json!({})).await.unwrap_err();
+        assert_eq!(format!("{err:#}"), "stopped by debugger");
+        assert_eq!(*debugger.stops.lock(), ["greet", "reply", "abort"]);
+        assert_eq!(*debugger.egress.lock(), json!([{ "text": "edited" }]));
+    }
+
+    handlebars::handlebars_helper!(shout: |text: str| format!("{}!", text.to_uppercase()));
+
+    #[tokio::test]
+    async fn payloads_render_with_builtin_and_host_helpers() {
+        let mut helpers = HelperRegistry::default();
+        helpers.register("shout", Box::new(shout));
+        let extensions = EngineExtensions {
+            helpers,
+            ..Default::default()
+        };
+        let engine = FlowEngine::with_extensions(Vec::new(), Arc::new(test_config()), extensions)
+            .await
+            .unwrap();
+        let ir = flow(
+            "main",
+            vec![(
+                "reply",
+                node(
+                    "qa.process",
+                    json!({
+                        "text": "{{shout input.name}} {{date input.since format=\"%b %Y\"}}",
+                        "total": "{{add input.count 1}}"
+                    }),
+                    Some("out"),
+                ),
+            )],
+        );
+        engine
+            .flow_ir
+            .write()
+            .insert(ir.id.clone(), PackFlow::from_ir(ir));
+
+        let execution = engine
+            .execute(
+                test_ctx("main"),
+                json!({ "name": "ada", "since": "2021-06-01T00:00:00Z", "count": 4 }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            execution.output,
+            json!({ "text": "ADA! Jun 2021", "total": "5" })
+        );
+    }
+
+    #[test]
+    fn unknown_components_are_rejected_at_load() {
+        let ir = flow(
+            "main",
+            vec![
+                ("start", node("qa.process", json!({}), Some("lookup"))),
+                ("lookup", node("crm.lookup", json!({}), Some("reply"))),
+                ("reply", node("emit.response", json!({}), Some("out"))),
+            ],
+        );
+        let flow = PackFlow::from_ir(ir);
+        let nodes = NodeRegistry::default();
+        let err =
+            check_components("main", &flow, |component| nodes.supports(component)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "flow main uses unsupported node components: lookup (crm.lookup)"
+        );
+
+        // A pack exporting the component satisfies the check as well.
+        let pack_nodes = HashMap::from([("crm.lookup".to_string(), 0usize)]);
+        check_components("main", &flow, |component| {
+            nodes.supports(component) || pack_nodes.contains_key(component)
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn refs_keep_json_types() {
+        let engine = Handlebars::new();
+        let context = json!({
+            "input": { "count": 3, "flag": true },
+            "nodes": { "lookup": { "payload": { "items": [1, 2], "owner": { "id": "u-1" } } } }
+        });
+        let payload = json!({
+            "items": { "$ref": "nodes.lookup.payload.items" },
+            "first": { "$ref": "nodes.lookup.payload.items.0" },
+            "owner": { "$ref": "/nodes/lookup/payload/owner" },
+            "count": { "$ref": "input.count" },
+            "flag": { "$ref": "input.flag" },
+            "missing": { "$ref": "input.nope", "default": [] },
+            "label": "{{input.count}} items",
+            "schema": { "$ref": "#/definitions/order", "type": "object" },
+            "bare_schema": { "$ref": "#/definitions/order" },
+            "remote_schema": { "$ref": "https://example.com/order.json" }
+        });
+        let resolved = resolve_template_value(&engine, &payload, &context).unwrap();
+        assert_eq!(
+            resolved,
+            json!({
+                "items": [1, 2],
+                "first": 1,
+                "owner": { "id": "u-1" },
+                "count": 3,
+                "flag": true,
+                "missing": [],
+                "label": "3 items",
+                "schema": { "$ref": "#/definitions/order", "type": "object" },
+                "bare_schema": { "$ref": "#/definitions/order" },
+                "remote_schema": { "$ref": "https://example.com/order.json" }
+            })
+        );
+    }
 
     #[test]
     fn templating_renders_with_partials_and_data() {
@@ -796,10 +3651,127 @@ pub struct FlowContext<'a> {
     pub tool: Option<&'a str>,
     pub action: Option<&'a str>,
     pub session_id: Option<&'a str>,
+    /// User behind the activity; owner of `user`-scoped state.
+    pub user_id: Option<&'a str>,
     pub provider_id: Option<&'a str>,
     pub retry_config: RetryConfig,
     pub observer: Option<&'a dyn ExecutionObserver>,
     pub mocks: Option<&'a MockLayer>,
+    /// Absolute wall-clock deadline for the run, shared with sub-flows.
+    pub deadline: Option<Instant>,
+    /// Upper bound for a single node dispatch.
+    pub node_timeout: Option<Duration>,
+    /// Node executions left for the run, shared with sub-flows.
+    pub node_budget: NodeBudget,
+    /// Token that resumes this session via `POST /resume/{token}`; exposed to templates as
+    /// `{{resume_token}}`.
+    pub resume_token: Option<&'a str>,
+    /// Subscriber that receives `emit*` payloads as they run. Streamed payloads are left out
+    /// of the buffered output; without a subscriber they are buffered as before.
+    pub egress: Option<&'a EgressSender>,
+    /// Cancels the run, sub-flows included; checked before each node and raced against
+    /// every dispatch.
+    pub cancel: CancellationToken,
+    /// Journal the run's nodes are recorded to, or replayed from.
+    pub journal: Option<&'a Journal>,
+    /// Pauses the run before each node; see [`FlowDebugger`].
+    pub debugger: Option<&'a dyn FlowDebugger>,
+}
+
+impl<'a> FlowContext<'a> {
+    /// Context for a sub-flow started by `flow.call` (or resumed inside one).
+    pub fn child<'b>(&'b self, flow_id: &'b str) -> FlowContext<'b> {
+        FlowContext {
+            tenant: self.tenant,
+            flow_id,
+            node_id: None,
+            tool: self.tool,
+            action: Some("flow.call"),
+            session_id: self.session_id,
+            user_id: self.user_id,
+            provider_id: self.provider_id,
+            retry_config: self.retry_config,
+            observer: self.observer,
+            mocks: self.mocks,
+            deadline: self.deadline,
+            node_timeout: self.node_timeout,
+            node_budget: self.node_budget.clone(),
+            resume_token: self.resume_token,
+            egress: self.egress,
+            cancel: self.cancel.clone(),
+            journal: self.journal,
+            debugger: self.debugger,
+        }
+    }
+}
+
+/// A payload emitted by an `emit*` node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EgressItem {
+    pub flow_id: String,
+    pub node_id: String,
+    pub payload: Value,
+}
+
+/// Sending half of an egress subscription; see [`egress_channel`].
+#[derive(Clone, Debug)]
+pub struct EgressSender {
+    tx: mpsc::UnboundedSender<EgressItem>,
+}
+
+impl EgressSender {
+    /// Deliver `item`; `false` once the subscriber has gone away.
+    fn send(&self, item: EgressItem) -> bool {
+        self.tx.send(item).is_ok()
+    }
+}
+
+/// Subscribe to a run's egress as it happens. The receiver ends once the run, and every
+/// clone of the sender, is dropped.
+pub fn egress_channel() -> (EgressSender, mpsc::UnboundedReceiver<EgressItem>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (EgressSender { tx }, rx)
+}
+
+/// Caps how many nodes a run may execute so routing loops fail instead of spinning forever.
+/// Clones share the same counter.
+#[derive(Clone, Debug)]
+pub struct NodeBudget {
+    limit: u32,
+    used: Arc<AtomicU32>,
+}
+
+impl NodeBudget {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            used: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    pub fn used(&self) -> u32 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Charge one node execution, returning its 1-based visit number, or `None` once spent.
+    fn consume(&self) -> Option<u32> {
+        self.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                (used < self.limit).then_some(used + 1)
+            })
+            .ok()
+            .map(|used| used + 1)
+    }
+}
+
+impl Default for NodeBudget {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_NODE_VISITS)
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -808,9 +3780,89 @@ pub struct RetryConfig {
     pub base_delay_ms: u64,
 }
 
-fn should_retry(err: &anyhow::Error) -> bool {
-    let lower = err.to_string().to_lowercase();
-    lower.contains("transient") || lower.contains("unavailable") || lower.contains("internal")
+/// Raised when a node or the whole run outlives its wall-clock budget.
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum DeadlineExceeded {
+    #[error("node {node_id} timed out after {timeout_ms}ms")]
+    Node { node_id: String, timeout_ms: u64 },
+    #[error("run deadline exceeded while executing node {node_id}")]
+    Run { node_id: String },
+}
+
+/// Raised when a run's [`CancellationToken`] fires.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("run cancelled at node {node_id} in flow {flow_id}")]
+pub struct FlowCancelled {
+    pub flow_id: String,
+    pub node_id: String,
+}
+
+fn cancelled(ctx: &FlowContext<'_>, node_id: &str) -> FlowCancelled {
+    FlowCancelled {
+        flow_id: ctx.flow_id.to_string(),
+        node_id: node_id.to_string(),
+    }
+}
+
+/// Raised when a run executes more nodes than its [`NodeBudget`] allows.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error(
+    "node visit budget of {limit} exhausted at node {node_id} in flow {flow_id}; the flow is probably looping"
+)]
+pub struct NodeBudgetExceeded {
+    pub flow_id: String,
+    pub node_id: String,
+    pub limit: u32,
+}
+
+/// Reject flows that reference components the engine cannot dispatch.
+fn check_components(flow_id: &str, flow: &PackFlow, supports: impl Fn(&str) -> bool) -> Result<()> {
+    let unknown = flow
+        .ir
+        .nodes
+        .iter()
+        .filter(|(_, node)| !supports(&node.component))
+        .map(|(node_id, node)| format!("{node_id} ({})", node.component))
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        bail!(
+            "flow {flow_id} uses unsupported node components: {}",
+            unknown.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn is_composite(component: &str) -> bool {
+    matches!(component, "flow.call" | "flow.parallel" | "flow.map")
+}
+
+/// Components without effects outside execution state; a replay runs them for real. `emit*`
+/// nodes only add to the run's egress during a replay, they never stream it.
+fn replays_live(component: &str) -> bool {
+    component.starts_with("emit")
+        || matches!(
+            component,
+            "session.wait" | "qa.process" | "templating.handlebars"
+        )
+}
+
+/// Backoff before the next attempt, or `None` when the failure should surface: the error is
+/// not retryable, attempts are exhausted, or the wait would overrun the run deadline.
+fn retry_delay(
+    policy: RetryConfig,
+    attempt: u32,
+    kind: ErrorKind,
+    deadline: Option<Instant>,
+) -> Option<Duration> {
+    if !kind.is_retryable() || attempt >= policy.max_attempts {
+        return None;
+    }
+    let delay = Duration::from_millis(backoff_delay_ms(policy.base_delay_ms, attempt - 1));
+    match deadline {
+        Some(deadline) if Instant::now() + delay >= deadline => None,
+        _ => Some(delay),
+    }
 }
 
 impl From<McpRetryConfig> for RetryConfig {
@@ -0,0 +1,153 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::engine::{DeadlineExceeded, FlowCancelled, NodeBudgetExceeded};
+
+/// Coarse classification of node failures, used for retries and error metadata.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Worth retrying: the same call may succeed later (store hiccups, tool crashes, rate limits).
+    Transient,
+    /// Retrying will not help (bad payloads, unknown tools or actions, template errors).
+    Permanent,
+    /// Rejected by host policy (unverified artifacts, denied secrets or egress).
+    Policy,
+    /// A node or run deadline expired.
+    Timeout,
+    /// The run was cancelled while the node ran.
+    Cancelled,
+}
+
+impl ErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Transient => "transient",
+            ErrorKind::Permanent => "permanent",
+            ErrorKind::Policy => "policy",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_retryable(self) -> bool {
+        matches!(self, ErrorKind::Transient)
+    }
+
+    /// Map a tool-reported error code onto a kind.
+    pub fn from_code(code: &str) -> Self {
+        match code.to_ascii_lowercase().as_str() {
+            "transient" | "unavailable" | "internal" | "rate_limited" | "busy" => {
+                ErrorKind::Transient
+            }
+            "timeout" | "deadline_exceeded" => ErrorKind::Timeout,
+            "forbidden" | "unauthorized" | "denied" | "policy" => ErrorKind::Policy,
+            "cancelled" | "canceled" => ErrorKind::Cancelled,
+            _ => ErrorKind::Permanent,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Wraps a node failure with its [`ErrorKind`] while keeping the original message and chain.
+#[derive(Debug)]
+pub struct NodeError {
+    kind: ErrorKind,
+    inner: anyhow::Error,
+}
+
+impl NodeError {
+    pub fn new(kind: ErrorKind, inner: impl Into<anyhow::Error>) -> Self {
+        Self {
+            kind,
+            inner: inner.into(),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+impl StdError for NodeError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.inner.source()
+    }
+}
+
+/// Classify an error chain: the outermost [`NodeError`] wins, deadlines are timeouts, an
+/// exhausted node budget is a policy failure, cancellation is its own kind and anything
+/// unclassified is permanent.
+pub fn classify(err: &anyhow::Error) -> ErrorKind {
+    err.chain()
+        .find_map(|cause| {
+            if let Some(node) = cause.downcast_ref::<NodeError>() {
+                Some(node.kind)
+            } else if cause.is::<DeadlineExceeded>() {
+                Some(ErrorKind::Timeout)
+            } else if cause.is::<NodeBudgetExceeded>() {
+                Some(ErrorKind::Policy)
+            } else if cause.is::<FlowCancelled>() {
+                Some(ErrorKind::Cancelled)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(ErrorKind::Permanent)
+}
+
+#[cfg(feature = "mcp")]
+pub(crate) fn classify_exec(err: &greentic_mcp::ExecError) -> ErrorKind {
+    use greentic_mcp::ExecError;
+
+    match err {
+        ExecError::Resolve { .. } | ExecError::Runner { .. } => ErrorKind::Transient,
+        ExecError::Verification { .. } => ErrorKind::Policy,
+        ExecError::NotFound { .. } => ErrorKind::Permanent,
+        ExecError::Tool { code, .. } => ErrorKind::from_code(code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn classify_sees_through_context() {
+        let err = anyhow::Error::new(NodeError::new(ErrorKind::Transient, anyhow!("store down")))
+            .context("mcp.exec failed");
+        assert_eq!(classify(&err), ErrorKind::Transient);
+        assert_eq!(format!("{err:#}"), "mcp.exec failed: store down");
+    }
+
+    #[test]
+    fn deadlines_are_timeouts_and_the_rest_is_permanent() {
+        let err = anyhow::Error::new(DeadlineExceeded::Node {
+            node_id: "fetch".into(),
+            timeout_ms: 10,
+        });
+        assert_eq!(classify(&err), ErrorKind::Timeout);
+        assert_eq!(classify(&anyhow!("internal error")), ErrorKind::Permanent);
+    }
+
+    #[test]
+    fn tool_codes_map_to_kinds() {
+        assert_eq!(ErrorKind::from_code("UNAVAILABLE"), ErrorKind::Transient);
+        assert_eq!(ErrorKind::from_code("denied"), ErrorKind::Policy);
+        assert_eq!(ErrorKind::from_code("bad_input"), ErrorKind::Permanent);
+    }
+}
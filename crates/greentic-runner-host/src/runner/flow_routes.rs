@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+
+use greentic_flow::ir::FlowIR;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Host-side view of a single routing entry.
+///
+/// `greentic_flow::ir::RouteIR` only carries `to`/`out`, so the extra keys are
+/// read straight from the raw flow document when the pack provides one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteSpec {
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub out: bool,
+    /// Condition rendered against the execution context; the route matches when truthy.
+    #[serde(default)]
+    pub when: Option<Value>,
+    /// Fallback route taken only when no other route of the node matches.
+    #[serde(default, alias = "else", deserialize_with = "deserialize_flag")]
+    pub default: bool,
+    /// Error route: only considered when the node failed.
+    #[serde(default, alias = "error", deserialize_with = "deserialize_flag")]
+    pub on_error: bool,
+    /// Timeout route of a `session.wait` node: followed when the wait expires.
+    #[serde(default, alias = "timeout", deserialize_with = "deserialize_flag")]
+    pub on_timeout: bool,
+}
+
+impl RouteSpec {
+    pub fn is_exit(&self) -> bool {
+        self.out || matches!(self.to.as_deref(), Some("out"))
+    }
+
+    fn is_target(&self) -> bool {
+        self.is_exit() || self.to.is_some()
+    }
+}
+
+/// Routing table for every node of a flow, keyed by node id.
+#[derive(Clone, Debug, Default)]
+pub struct FlowRoutes {
+    nodes: HashMap<String, Vec<RouteSpec>>,
+}
+
+impl FlowRoutes {
+    /// Build the table from the plain IR (no conditions available).
+    pub fn from_ir(ir: &FlowIR) -> Self {
+        let nodes = ir
+            .nodes
+            .iter()
+            .map(|(id, node)| {
+                let routes = node
+                    .routes
+                    .iter()
+                    .map(|route| RouteSpec {
+                        to: route.to.clone(),
+                        out: route.out,
+                        ..RouteSpec::default()
+                    })
+                    .collect();
+                (id.clone(), routes)
+            })
+            .collect();
+        Self { nodes }
+    }
+
+    /// Build the table from the raw flow document, falling back to the IR for nodes
+    /// whose routing block cannot be decoded.
+    pub fn from_document(ir: &FlowIR, doc: &Value) -> Self {
+        let mut table = Self::from_ir(ir);
+        let Some(nodes) = doc.get("nodes").and_then(Value::as_object) else {
+            return table;
+        };
+        for (id, node) in nodes {
+            let Some(routing) = node.get("routing") else {
+                continue;
+            };
+            match serde_json::from_value::<Vec<RouteSpec>>(routing.clone()) {
+                Ok(routes) if table.nodes.contains_key(id) => {
+                    table.nodes.insert(id.clone(), routes);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    tracing::warn!(
+                        flow_id = %ir.id,
+                        node_id = %id,
+                        error = %err,
+                        "ignoring unparseable routing extensions"
+                    );
+                }
+            }
+        }
+        table
+    }
+
+    pub fn routes(&self, node_id: &str) -> &[RouteSpec] {
+        self.nodes
+            .get(node_id)
+            .map(|routes| routes.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn has_error_route(&self, node_id: &str) -> bool {
+        self.routes(node_id).iter().any(|route| route.on_error)
+    }
+
+    /// Node to resume at when a wait on `node_id` expires, taken from its first timeout route.
+    pub fn timeout_target(&self, node_id: &str) -> Option<&str> {
+        self.routes(node_id)
+            .iter()
+            .filter(|route| route.on_timeout && !route.is_exit())
+            .find_map(|route| route.to.as_deref())
+    }
+
+    /// Pick the route to follow: the first matching non-default route wins, otherwise the
+    /// first default route. Only error routes are considered when `failed` is set, and only
+    /// regular routes otherwise; timeout routes never are. `matches` evaluates a route condition.
+    pub fn select<F, E>(
+        &self,
+        node_id: &str,
+        failed: bool,
+        mut matches: F,
+    ) -> Result<Option<&RouteSpec>, E>
+    where
+        F: FnMut(&Value) -> Result<bool, E>,
+    {
+        let routes: Vec<&RouteSpec> = self
+            .routes(node_id)
+            .iter()
+            .filter(|route| route.on_error == failed && !route.on_timeout)
+            .collect();
+        for route in routes.iter().filter(|route| !route.default) {
+            if !route.is_target() {
+                continue;
+            }
+            let matched = match &route.when {
+                Some(condition) => matches(condition)?,
+                None => true,
+            };
+            if matched {
+                return Ok(Some(route));
+            }
+        }
+        Ok(routes
+            .into_iter()
+            .find(|route| route.default && route.is_target()))
+    }
+}
+
+/// Interpret a rendered condition value using template-friendly truthiness.
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(flag) => *flag,
+        Value::Number(number) => number.as_f64().map(|n| n != 0.0).unwrap_or(true),
+        Value::String(text) => {
+            let trimmed = text.trim();
+            !(trimmed.is_empty()
+                || trimmed.eq_ignore_ascii_case("false")
+                || trimmed == "0"
+                || trimmed.eq_ignore_ascii_case("null"))
+        }
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn deserialize_flag<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<bool>::deserialize(deserializer)?.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use greentic_flow::ir::{NodeIR, RouteIR};
+    use indexmap::IndexMap;
+    use serde_json::json;
+
+    fn sample_ir() -> FlowIR {
+        let mut nodes = IndexMap::new();
+        nodes.insert(
+            "ask".to_string(),
+            NodeIR {
+                component: "qa.process".into(),
+                payload_expr: json!({}),
+                routes: vec![
+                    RouteIR {
+                        to: Some("yes".into()),
+                        out: false,
+                    },
+                    RouteIR {
+                        to: Some("no".into()),
+                        out: false,
+                    },
+                ],
+            },
+        );
+        FlowIR {
+            id: "flow".into(),
+            flow_type: "messaging".into(),
+            start: Some("ask".into()),
+            parameters: json!({}),
+            nodes,
+        }
+    }
+
+    #[test]
+    fn document_routes_carry_conditions() {
+        let ir = sample_ir();
+        let doc = json!({
+            "nodes": {
+                "ask": {
+                    "routing": [
+                        { "to": "no", "default": true },
+                        { "to": "yes", "when": "{{eq input.text \"yes\"}}" }
+                    ]
+                }
+            }
+        });
+        let routes = FlowRoutes::from_document(&ir, &doc);
+        let picked = routes
+            .select::<_, ()>("ask", false, |_| Ok(false))
+            .unwrap()
+            .unwrap();
+        assert_eq!(picked.to.as_deref(), Some("no"));
+        let picked = routes
+            .select::<_, ()>("ask", false, |_| Ok(true))
+            .unwrap()
+            .unwrap();
+        assert_eq!(picked.to.as_deref(), Some("yes"));
+    }
+
+    #[test]
+    fn plain_ir_keeps_first_route() {
+        let routes = FlowRoutes::from_ir(&sample_ir());
+        let picked = routes
+            .select::<_, ()>("ask", false, |_| Ok(false))
+            .unwrap()
+            .unwrap();
+        assert_eq!(picked.to.as_deref(), Some("yes"));
+    }
+
+    #[test]
+    fn error_routes_are_kept_apart() {
+        let ir = sample_ir();
+        let doc = json!({
+            "nodes": {
+                "ask": {
+                    "routing": [
+                        { "to": "yes" },
+                        { "to": "no", "on_error": true }
+                    ]
+                }
+            }
+        });
+        let routes = FlowRoutes::from_document(&ir, &doc);
+        assert!(routes.has_error_route("ask"));
+        let ok = routes.select::<_, ()>("ask", false, |_| Ok(true)).unwrap();
+        assert_eq!(ok.and_then(|route| route.to.as_deref()), Some("yes"));
+        let failed = routes.select::<_, ()>("ask", true, |_| Ok(true)).unwrap();
+        assert_eq!(failed.and_then(|route| route.to.as_deref()), Some("no"));
+        assert!(!FlowRoutes::from_ir(&ir).has_error_route("ask"));
+    }
+
+    #[test]
+    fn timeout_routes_are_only_used_on_expiry() {
+        let ir = sample_ir();
+        let doc = json!({
+            "nodes": {
+                "ask": {
+                    "routing": [
+                        { "to": "no", "on_timeout": true },
+                        { "to": "yes" }
+                    ]
+                }
+            }
+        });
+        let routes = FlowRoutes::from_document(&ir, &doc);
+        let picked = routes.select::<_, ()>("ask", false, |_| Ok(true)).unwrap();
+        assert_eq!(picked.and_then(|route| route.to.as_deref()), Some("yes"));
+        assert_eq!(routes.timeout_target("ask"), Some("no"));
+        assert_eq!(FlowRoutes::from_ir(&ir).timeout_target("ask"), None);
+    }
+
+    #[test]
+    fn truthiness_follows_template_output() {
+        assert!(is_truthy(&json!("true")));
+        assert!(is_truthy(&json!(" yes ")));
+        assert!(!is_truthy(&json!("false")));
+        assert!(!is_truthy(&json!("")));
+        assert!(!is_truthy(&json!(0)));
+        assert!(!is_truthy(&Value::Null));
+        assert!(is_truthy(&json!([1])));
+    }
+}
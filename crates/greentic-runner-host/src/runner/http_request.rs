@@ -0,0 +1,296 @@
+//! Outbound HTTP shared by the `http-fetch` host import and the built-in `http.request` node.
+//!
+//! Both paths go through the same gate: `http_enabled` from the bindings first, then the mock
+//! layer, which replays cassettes, enforces the network allowlist and records live responses.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method, blocking::Client as BlockingClient};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::errors::{ErrorKind, NodeError};
+use super::mocks::{HttpDecision, HttpMockRequest, HttpMockResponse, MockLayer};
+
+/// An outbound request, already rendered.
+#[derive(Clone, Debug)]
+pub struct HttpCall {
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpFetchError {
+    #[error("http request to {url} denied: {reason}")]
+    Denied { url: String, reason: String },
+    #[error("http request to {url} timed out")]
+    TimedOut { url: String },
+    #[error("http request to {url} failed: {reason}")]
+    Unavailable { url: String, reason: String },
+}
+
+impl HttpFetchError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            HttpFetchError::Denied { .. } => ErrorKind::Policy,
+            HttpFetchError::TimedOut { .. } => ErrorKind::Timeout,
+            HttpFetchError::Unavailable { .. } => ErrorKind::Transient,
+        }
+    }
+}
+
+enum Gate {
+    /// Answered by a recorded cassette.
+    Replay(HttpMockResponse),
+    /// Send for real; record the response under this request when set.
+    Send(Option<HttpMockRequest>),
+}
+
+fn gate(
+    http_enabled: bool,
+    mocks: Option<&MockLayer>,
+    call: &HttpCall,
+) -> Result<Gate, HttpFetchError> {
+    if !http_enabled {
+        return Err(HttpFetchError::Denied {
+            url: call.url.clone(),
+            reason: "http is disabled by bindings policy".into(),
+        });
+    }
+    let Some(mock) = mocks else {
+        return Ok(Gate::Send(None));
+    };
+    let Ok(meta) = HttpMockRequest::new(
+        &call.method,
+        &call.url,
+        call.body.as_deref().map(str::as_bytes),
+    ) else {
+        return Ok(Gate::Send(None));
+    };
+    match mock.http_begin(&meta) {
+        HttpDecision::Mock(response) => Ok(Gate::Replay(response)),
+        HttpDecision::Deny(reason) => Err(HttpFetchError::Denied {
+            url: call.url.clone(),
+            reason,
+        }),
+        HttpDecision::Passthrough { record } => Ok(Gate::Send(record.then_some(meta))),
+    }
+}
+
+fn finish(mocks: Option<&MockLayer>, record: Option<HttpMockRequest>, reply: &HttpMockResponse) {
+    if let (Some(meta), Some(mock)) = (record, mocks) {
+        mock.http_record(&meta, reply);
+    }
+}
+
+/// Send `call` on a blocking client; used from Wasm host imports.
+pub fn fetch_blocking(
+    http_enabled: bool,
+    mocks: Option<&MockLayer>,
+    client: &BlockingClient,
+    call: &HttpCall,
+) -> Result<HttpMockResponse, HttpFetchError> {
+    let record = match gate(http_enabled, mocks, call)? {
+        Gate::Replay(response) => return Ok(response),
+        Gate::Send(record) => record,
+    };
+    let mut builder = client
+        .request(parse_method(&call.method), &call.url)
+        .headers(header_map(&call.headers));
+    if let Some(body) = &call.body {
+        builder = builder.body(body.clone());
+    }
+    if let Some(timeout) = call.timeout {
+        builder = builder.timeout(timeout);
+    }
+    let response = builder.send().map_err(|err| send_error(call, err))?;
+    let status = response.status().as_u16();
+    let headers = response_headers(response.headers());
+    let reply = HttpMockResponse::new(status, headers, response.text().ok());
+    finish(mocks, record, &reply);
+    Ok(reply)
+}
+
+/// Send `call` on an async client; used by the `http.request` node.
+pub async fn fetch(
+    http_enabled: bool,
+    mocks: Option<&MockLayer>,
+    client: &Client,
+    call: &HttpCall,
+) -> Result<HttpMockResponse, HttpFetchError> {
+    let record = match gate(http_enabled, mocks, call)? {
+        Gate::Replay(response) => return Ok(response),
+        Gate::Send(record) => record,
+    };
+    let mut builder = client
+        .request(parse_method(&call.method), &call.url)
+        .headers(header_map(&call.headers));
+    if let Some(body) = &call.body {
+        builder = builder.body(body.clone());
+    }
+    if let Some(timeout) = call.timeout {
+        builder = builder.timeout(timeout);
+    }
+    let response = builder.send().await.map_err(|err| send_error(call, err))?;
+    let status = response.status().as_u16();
+    let headers = response_headers(response.headers());
+    let reply = HttpMockResponse::new(status, headers, response.text().await.ok());
+    finish(mocks, record, &reply);
+    Ok(reply)
+}
+
+fn parse_method(method: &str) -> Method {
+    method.parse().unwrap_or(Method::GET)
+}
+
+/// Headers with invalid names or values are skipped.
+fn header_map(headers: &BTreeMap<String, String>) -> HeaderMap {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((
+                HeaderName::from_bytes(name.as_bytes()).ok()?,
+                HeaderValue::from_str(value).ok()?,
+            ))
+        })
+        .collect()
+}
+
+fn response_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str().to_string(),
+                value.to_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn send_error(call: &HttpCall, err: reqwest::Error) -> HttpFetchError {
+    if err.is_timeout() {
+        HttpFetchError::TimedOut {
+            url: call.url.clone(),
+        }
+    } else {
+        HttpFetchError::Unavailable {
+            url: call.url.clone(),
+            reason: err.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct HttpNodePayload {
+    url: String,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Strings are sent as-is; any other JSON value is encoded as a JSON body.
+    #[serde(default)]
+    body: Value,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+}
+
+fn default_method() -> String {
+    "GET".into()
+}
+
+/// Run an `http.request` node. Responses of any status become the node output; only denied,
+/// timed-out or failed requests fail the node.
+pub(crate) async fn execute(
+    http_enabled: bool,
+    mocks: Option<&MockLayer>,
+    client: &Client,
+    node_id: &str,
+    payload: Value,
+) -> Result<Value> {
+    let payload: HttpNodePayload = serde_json::from_value(payload)
+        .with_context(|| format!("invalid payload for http.request node {node_id}"))?;
+    let method = payload.method.to_ascii_uppercase();
+    Method::from_bytes(method.as_bytes())
+        .map_err(|_| anyhow!("http.request node {node_id} has invalid method {method}"))?;
+    let mut headers = payload.headers;
+    let body = match payload.body {
+        Value::Null => None,
+        Value::String(text) => Some(text),
+        other => {
+            if !headers
+                .keys()
+                .any(|name| name.eq_ignore_ascii_case("content-type"))
+            {
+                headers.insert("content-type".into(), "application/json".into());
+            }
+            Some(serde_json::to_string(&other)?)
+        }
+    };
+    let call = HttpCall {
+        method,
+        url: payload.url,
+        headers,
+        body,
+        timeout: payload.timeout_ms.map(Duration::from_millis),
+    };
+
+    let response = fetch(http_enabled, mocks, client, &call)
+        .await
+        .map_err(|err| NodeError::new(err.kind(), err))?;
+    let is_json = response
+        .headers
+        .iter()
+        .any(|(name, value)| name.eq_ignore_ascii_case("content-type") && value.contains("json"));
+    let body = match response.body {
+        Some(text) if is_json => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        Some(text) => Value::String(text),
+        None => Value::Null,
+    };
+    Ok(json!({
+        "status": response.status,
+        "headers": response.headers,
+        "body": body,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(url: &str) -> HttpCall {
+        HttpCall {
+            method: "GET".into(),
+            url: url.into(),
+            headers: BTreeMap::new(),
+            body: None,
+            timeout: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled_http_is_denied_as_policy() {
+        let client = Client::new();
+        let err = fetch(false, None, &client, &call("https://example.test/"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Policy);
+    }
+
+    #[test]
+    fn invalid_headers_are_skipped() {
+        let headers = BTreeMap::from([
+            ("x-ok".to_string(), "yes".to_string()),
+            ("bad header".to_string(), "no".to_string()),
+        ]);
+        let map = header_map(&headers);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["x-ok"], "yes");
+    }
+}
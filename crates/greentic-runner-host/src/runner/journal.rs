@@ -0,0 +1,213 @@
+//! Execution journals: a record of every node a run dispatched, and deterministic replay of it.
+//!
+//! A recording [`Journal`] keeps each node's rendered payload (secrets redacted), its outcome
+//! and the host imports (`http_fetch`, `mcp.exec`) a pack node made along the way. A replaying
+//! journal hands those outcomes back instead of dispatching, so `drive_flow` walks the same
+//! path without touching tools, HTTP or stores. Nodes that only touch execution state
+//! (`emit*`, `session.wait`, `qa.process`, `templating.handlebars`) still run for real;
+//! composite nodes are re-driven and their inner nodes replayed.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::errors::ErrorKind;
+
+/// A host import made by a pack node while it ran.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HostCall {
+    pub import: String,
+    pub request: Value,
+    pub response: Value,
+}
+
+/// Where a pack call collects its [`HostCall`]s.
+pub type HostCallLog = Arc<Mutex<Vec<HostCall>>>;
+
+/// How a journaled node ended.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JournalOutcome {
+    Ok { output: Value },
+    Failed { kind: ErrorKind, message: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub flow_id: String,
+    pub node_id: String,
+    pub component: String,
+    /// Rendered payload with resolved secrets redacted.
+    pub payload: Value,
+    pub outcome: JournalOutcome,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub host_calls: Vec<HostCall>,
+}
+
+/// Raised when a replayed run asks for a node the journal cannot answer.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("replay diverged at node {node_id} in flow {flow_id}: {reason}")]
+pub struct ReplayDivergence {
+    pub flow_id: String,
+    pub node_id: String,
+    pub reason: String,
+}
+
+enum Mode {
+    Record(Vec<JournalEntry>),
+    /// Entries not replayed yet, in recorded order.
+    Replay(Vec<Option<JournalEntry>>),
+}
+
+/// A run's journal, either being recorded or being replayed. Shared by sub-flows and
+/// parallel branches through [`FlowContext::journal`](super::engine::FlowContext::journal).
+pub struct Journal {
+    mode: Mutex<Mode>,
+}
+
+impl Journal {
+    pub fn recording() -> Self {
+        Self {
+            mode: Mutex::new(Mode::Record(Vec::new())),
+        }
+    }
+
+    pub fn replaying(entries: Vec<JournalEntry>) -> Self {
+        Self {
+            mode: Mutex::new(Mode::Replay(entries.into_iter().map(Some).collect())),
+        }
+    }
+
+    /// Replay the journal written by [`Journal::save`] at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            fs::read(path).with_context(|| format!("failed to read journal {}", path.display()))?;
+        let entries = serde_json::from_slice(&bytes)
+            .with_context(|| format!("invalid journal {}", path.display()))?;
+        Ok(Self::replaying(entries))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(&self.entries())?)
+            .with_context(|| format!("failed to write journal {}", path.display()))
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(*self.mode.lock(), Mode::Replay(_))
+    }
+
+    /// Recorded entries, or the entries still waiting to be replayed.
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        match &*self.mode.lock() {
+            Mode::Record(entries) => entries.clone(),
+            Mode::Replay(entries) => entries.iter().flatten().cloned().collect(),
+        }
+    }
+
+    pub(crate) fn record(&self, entry: JournalEntry) {
+        if let Mode::Record(entries) = &mut *self.mode.lock() {
+            entries.push(entry);
+        }
+    }
+
+    /// Take the first unreplayed entry for `node_id` in `flow_id` that was rendered with
+    /// `payload`. Matching on the payload keeps parallel branches and map items apart even
+    /// when they finish in a different order than recorded.
+    pub(crate) fn replay(
+        &self,
+        flow_id: &str,
+        node_id: &str,
+        payload: &Value,
+    ) -> Result<JournalEntry, ReplayDivergence> {
+        let divergence = |reason: &str| ReplayDivergence {
+            flow_id: flow_id.to_string(),
+            node_id: node_id.to_string(),
+            reason: reason.to_string(),
+        };
+        let mut mode = self.mode.lock();
+        let Mode::Replay(entries) = &mut *mode else {
+            return Err(divergence("journal is recording, not replaying"));
+        };
+        let mut same_node = entries
+            .iter_mut()
+            .filter(|slot| {
+                slot.as_ref()
+                    .is_some_and(|entry| entry.flow_id == flow_id && entry.node_id == node_id)
+            })
+            .peekable();
+        if same_node.peek().is_none() {
+            return Err(divergence("the node never ran in the recorded execution"));
+        }
+        same_node
+            .find(|slot| slot.as_ref().is_some_and(|entry| &entry.payload == payload))
+            .and_then(Option::take)
+            .ok_or_else(|| divergence("rendered payload differs from the recording"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(node_id: &str, payload: Value, output: Value) -> JournalEntry {
+        JournalEntry {
+            flow_id: "main".into(),
+            node_id: node_id.into(),
+            component: "crm.lookup".into(),
+            payload,
+            outcome: JournalOutcome::Ok { output },
+            host_calls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn replay_matches_entries_by_payload() {
+        let journal = Journal::replaying(vec![
+            entry("lookup", json!({ "id": 1 }), json!("one")),
+            entry("lookup", json!({ "id": 2 }), json!("two")),
+        ]);
+        let second = journal
+            .replay("main", "lookup", &json!({ "id": 2 }))
+            .unwrap();
+        assert_eq!(
+            second.outcome,
+            JournalOutcome::Ok {
+                output: json!("two")
+            }
+        );
+        assert_eq!(journal.entries().len(), 1);
+
+        let err = journal
+            .replay("main", "lookup", &json!({ "id": 3 }))
+            .unwrap_err();
+        assert!(err.reason.contains("payload differs"));
+        assert!(journal.replay("main", "reply", &json!({})).is_err());
+    }
+
+    #[test]
+    fn entries_roundtrip_through_json() {
+        let recorded = JournalEntry {
+            outcome: JournalOutcome::Failed {
+                kind: ErrorKind::Transient,
+                message: "store down".into(),
+            },
+            host_calls: vec![HostCall {
+                import: "http_fetch".into(),
+                request: json!({ "url": "https://example.test" }),
+                response: json!({ "status": 503 }),
+            }],
+            ..entry("lookup", json!({}), Value::Null)
+        };
+        let text = serde_json::to_string(&recorded).unwrap();
+        assert_eq!(
+            serde_json::from_str::<JournalEntry>(&text).unwrap(),
+            recorded
+        );
+    }
+}
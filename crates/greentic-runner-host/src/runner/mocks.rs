@@ -336,7 +336,7 @@ impl HttpMockRuntime {
             Some(dir) => dir,
             None => return Ok(()),
         };
-        let path = dir.join(format!("{}.json", &req.fingerprint));
+        let path = dir.join(format!("{}.json", req.fingerprint));
         let mut file = fs::File::create(&path)
             .with_context(|| format!("failed to write {}", path.display()))?;
         let body = serde_json::to_vec_pretty(resp)?;
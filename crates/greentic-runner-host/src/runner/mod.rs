@@ -1,4 +1,6 @@
+pub mod active_runs;
 pub mod adapt_messaging;
+pub mod adapt_resume;
 pub mod adapt_slack;
 pub mod adapt_teams;
 pub mod adapt_timer;
@@ -7,8 +9,18 @@ pub mod adapt_webex;
 pub mod adapt_webhook;
 pub mod adapt_whatsapp;
 pub mod engine;
+pub mod errors;
+pub mod flow_routes;
+pub mod http_request;
 pub mod ingress_util;
+pub mod journal;
 pub mod mocks;
+pub mod nodes;
+pub mod pack_pins;
+pub mod resume_tokens;
+pub mod state_nodes;
+pub mod templating;
+pub mod wait_timeouts;
 
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -61,9 +73,11 @@ impl HostServer {
                 get(adapt_whatsapp::verify).post(adapt_whatsapp::webhook),
             )
             .route("/webhook/:flow_id", any(adapt_webhook::dispatch))
+            .route("/resume/:token", post(adapt_resume::resume))
             .route("/healthz", get(http::health::handler))
             .route("/admin/packs/status", get(admin::status))
             .route("/admin/packs/reload", post(admin::reload))
+            .route("/admin/runs/cancel", post(admin::cancel_runs))
             .with_state(state.clone());
         Ok(Self {
             addr,
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+
+/// Components implemented by the flow engine itself. Registered handlers never shadow these.
+const BUILTIN_COMPONENTS: &[&str] = &[
+    "qa.process",
+    "mcp.exec",
+    "templating.handlebars",
+    "flow.call",
+    "flow.parallel",
+    "flow.map",
+    "session.wait",
+    "http.request",
+    "state.get",
+    "state.set",
+    "state.delete",
+];
+
+/// Returns true when `component` is handled by the engine without a registered handler.
+pub fn is_builtin(component: &str) -> bool {
+    BUILTIN_COMPONENTS.contains(&component) || component.starts_with("emit")
+}
+
+/// What a [`NodeHandler`] gets to see about the node it is executing.
+pub struct NodeContext<'a> {
+    pub tenant: &'a str,
+    pub flow_id: &'a str,
+    pub node_id: &'a str,
+    pub component: &'a str,
+    pub session_id: Option<&'a str>,
+    pub provider_id: Option<&'a str>,
+    /// Template context of the run so far (`input` and prior `nodes` outputs).
+    pub state: &'a Value,
+}
+
+/// A node component supplied by the embedding application.
+///
+/// The payload has already been rendered. Errors follow the engine's usual handling: wrap
+/// them in [`NodeError`](super::errors::NodeError) to pick an [`ErrorKind`](super::errors::ErrorKind)
+/// (and so opt into retries), otherwise they count as permanent failures.
+#[async_trait]
+pub trait NodeHandler: Send + Sync {
+    async fn invoke(&self, ctx: &NodeContext<'_>, payload: Value) -> Result<Value>;
+}
+
+#[derive(Default, Clone)]
+pub struct NodeRegistry {
+    inner: HashMap<String, Arc<dyn NodeHandler>>,
+}
+
+impl NodeRegistry {
+    pub fn register(&mut self, component: impl Into<String>, handler: Box<dyn NodeHandler>) {
+        self.inner.insert(component.into(), Arc::from(handler));
+    }
+
+    pub fn register_arc(&mut self, component: impl Into<String>, handler: Arc<dyn NodeHandler>) {
+        self.inner.insert(component.into(), handler);
+    }
+
+    pub fn get(&self, component: &str) -> Option<Arc<dyn NodeHandler>> {
+        self.inner.get(component).cloned()
+    }
+
+    /// Whether the engine can execute `component`, either natively or through a handler.
+    pub fn supports(&self, component: &str) -> bool {
+        is_builtin(component) || self.inner.contains_key(component)
+    }
+}
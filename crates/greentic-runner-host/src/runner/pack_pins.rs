@@ -0,0 +1,126 @@
+//! Flow engines kept alive for sessions that are waiting on them.
+//!
+//! A session that pauses is pinned to the digest of the packs that saved its snapshot. When a
+//! reload replaces them, the new tenant runtime shares the registry with the old one, so the
+//! session is resumed on the engine it started on. An engine is released as soon as no
+//! session waits on it any more. Each pin also expires, so sessions that are never answered
+//! do not keep old engines alive forever; an expired session follows the bindings' resume
+//! policy like one whose pack is gone. Pins live in memory; after a restart, snapshots from
+//! packs that are no longer loaded fall back to that policy as well.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::engine::{FlowEngine, unix_millis};
+
+#[derive(Clone, Default)]
+pub struct PinnedEngines {
+    inner: Arc<Mutex<HashMap<String, Pin>>>,
+}
+
+struct Pin {
+    engine: Arc<FlowEngine>,
+    /// Waiting sessions and when their pin expires, in Unix milliseconds.
+    sessions: HashMap<String, u64>,
+}
+
+impl PinnedEngines {
+    /// Pin `session` to `engine`, loaded from the packs with `digest`, until it is released,
+    /// pinned elsewhere, or `expires_at_ms` passes.
+    pub fn pin(&self, session: &str, digest: &str, engine: &Arc<FlowEngine>, expires_at_ms: u64) {
+        let mut pins = self.inner.lock();
+        unpin(&mut pins, session);
+        pins.entry(digest.to_string())
+            .or_insert_with(|| Pin {
+                engine: Arc::clone(engine),
+                sessions: HashMap::new(),
+            })
+            .sessions
+            .insert(session.to_string(), expires_at_ms);
+    }
+
+    pub fn release(&self, session: &str) {
+        unpin(&mut self.inner.lock(), session);
+    }
+
+    /// The engine of the packs with `digest`, if a session still waits on it.
+    pub fn engine(&self, digest: &str) -> Option<Arc<FlowEngine>> {
+        let mut pins = self.inner.lock();
+        expire(&mut pins, unix_millis());
+        pins.get(digest).map(|pin| Arc::clone(&pin.engine))
+    }
+
+    /// Digests with waiting sessions, and how many sessions wait on each.
+    pub fn digests(&self) -> HashMap<String, usize> {
+        let mut pins = self.inner.lock();
+        expire(&mut pins, unix_millis());
+        pins.iter()
+            .map(|(digest, pin)| (digest.clone(), pin.sessions.len()))
+            .collect()
+    }
+}
+
+fn unpin(pins: &mut HashMap<String, Pin>, session: &str) {
+    pins.retain(|_, pin| {
+        pin.sessions.remove(session);
+        !pin.sessions.is_empty()
+    });
+}
+
+fn expire(pins: &mut HashMap<String, Pin>, now_ms: u64) {
+    pins.retain(|_, pin| {
+        pin.sessions
+            .retain(|_, expires_at_ms| *expires_at_ms > now_ms);
+        !pin.sessions.is_empty()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HostConfig;
+
+    async fn engine() -> Arc<FlowEngine> {
+        let config = HostConfig::load_from_path(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../examples/bindings/default.bindings.yaml"),
+        )
+        .unwrap();
+        Arc::new(FlowEngine::new(Vec::new(), Arc::new(config)).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn engines_stay_pinned_while_sessions_wait() {
+        let pins = PinnedEngines::default();
+        let old = engine().await;
+        let new = engine().await;
+        let later = unix_millis() + 60_000;
+        pins.pin("s1", "sha256:old", &old, later);
+        pins.pin("s2", "sha256:old", &old, later);
+        pins.pin("s1", "sha256:new", &new, later);
+        assert!(Arc::ptr_eq(&pins.engine("sha256:old").unwrap(), &old));
+        assert_eq!(pins.digests()["sha256:old"], 1);
+
+        pins.release("s2");
+        assert!(pins.engine("sha256:old").is_none());
+        assert!(pins.engine("sha256:new").is_some());
+        pins.release("s1");
+        assert!(pins.digests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn abandoned_pins_expire() {
+        let pins = PinnedEngines::default();
+        let old = engine().await;
+        let now = unix_millis();
+        pins.pin("abandoned", "sha256:old", &old, now - 1);
+        pins.pin("waiting", "sha256:old", &old, now + 60_000);
+        assert_eq!(pins.digests()["sha256:old"], 1);
+
+        pins.pin("waiting", "sha256:old", &old, now - 1);
+        assert!(pins.engine("sha256:old").is_none());
+        assert!(pins.digests().is_empty());
+    }
+}
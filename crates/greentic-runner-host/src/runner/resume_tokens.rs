@@ -0,0 +1,175 @@
+//! Opaque tokens that resume a waiting flow out of band (`POST /resume/{token}`).
+//!
+//! Every run of a flow gets a fresh token, handed to it as `{{resume_token}}`. The token is
+//! registered when the run pauses and stops working as soon as the wait is resumed, by
+//! token or by activity, so a leaked token only ever resumes the wait it was issued for.
+
+use greentic_state::StateKey;
+use greentic_types::TenantCtx;
+use rand::{Rng, rng};
+
+use crate::engine::error::{GResult, RunnerError};
+use crate::engine::runtime::IngressEnvelope;
+use crate::storage::state::{DynStateStore, host_tenant_ctx, map_state_error};
+
+const TOKEN_PREFIX: &str = "resume-token";
+const SESSION_PREFIX: &str = "resume-session";
+
+#[derive(Clone)]
+pub struct ResumeTokenStore {
+    store: DynStateStore,
+    tenant: TenantCtx,
+}
+
+impl ResumeTokenStore {
+    pub fn new(store: DynStateStore, tenant: &str) -> GResult<Self> {
+        Ok(Self {
+            store,
+            tenant: host_tenant_ctx(tenant)?,
+        })
+    }
+
+    /// Token already handed out for the wait filed under `wait_key`, if any.
+    pub fn token_for(&self, wait_key: &str) -> GResult<Option<String>> {
+        let value = self
+            .store
+            .get_json(
+                &self.tenant,
+                SESSION_PREFIX,
+                &StateKey::from(wait_key),
+                None,
+            )
+            .map_err(map_state_error)?;
+        Ok(value.and_then(|value| value.as_str().map(str::to_string)))
+    }
+
+    /// Make `token` resume the waiting flow of `envelope`, revoking the token of its previous wait.
+    pub fn register(&self, token: &str, envelope: &IngressEnvelope) -> GResult<()> {
+        self.revoke(&envelope.wait_key())?;
+        let value = serde_json::to_value(envelope).map_err(|err| RunnerError::State {
+            reason: format!("failed to encode resume token: {err}"),
+        })?;
+        self.store
+            .set_json(
+                &self.tenant,
+                TOKEN_PREFIX,
+                &StateKey::from(token),
+                None,
+                &value,
+                None,
+            )
+            .map_err(map_state_error)?;
+        self.store
+            .set_json(
+                &self.tenant,
+                SESSION_PREFIX,
+                &StateKey::from(envelope.wait_key()),
+                None,
+                &serde_json::Value::String(token.to_string()),
+                None,
+            )
+            .map_err(map_state_error)
+    }
+
+    /// Envelope of the waiting flow `token` resumes.
+    pub fn resolve(&self, token: &str) -> GResult<Option<IngressEnvelope>> {
+        let value = self
+            .store
+            .get_json(&self.tenant, TOKEN_PREFIX, &StateKey::from(token), None)
+            .map_err(map_state_error)?;
+        value
+            .map(|value| {
+                serde_json::from_value(value).map_err(|err| RunnerError::State {
+                    reason: format!("failed to decode resume token: {err}"),
+                })
+            })
+            .transpose()
+    }
+
+    /// Take ownership of `token` by deleting it, like
+    /// [`WaitTimeoutStore::claim`](super::wait_timeouts::WaitTimeoutStore::claim). Only one of
+    /// several concurrent callers gets the envelope back; the others see `None`.
+    pub fn claim(&self, token: &str) -> GResult<Option<IngressEnvelope>> {
+        let Some(envelope) = self.resolve(token)? else {
+            return Ok(None);
+        };
+        let claimed = self
+            .store
+            .del(&self.tenant, TOKEN_PREFIX, &StateKey::from(token))
+            .map_err(map_state_error)?;
+        Ok(claimed.then_some(envelope))
+    }
+
+    /// Forget the token of the wait filed under `wait_key`.
+    pub fn revoke(&self, wait_key: &str) -> GResult<()> {
+        let Some(token) = self.token_for(wait_key)? else {
+            return Ok(());
+        };
+        self.store
+            .del(&self.tenant, TOKEN_PREFIX, &StateKey::from(token.as_str()))
+            .map_err(map_state_error)?;
+        self.store
+            .del(&self.tenant, SESSION_PREFIX, &StateKey::from(wait_key))
+            .map_err(map_state_error)?;
+        Ok(())
+    }
+}
+
+/// A fresh 128-bit token, hex encoded.
+pub fn new_resume_token() -> String {
+    let bytes: [u8; 16] = rng().random();
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::state::new_state_store;
+    use serde_json::json;
+
+    #[test]
+    fn tokens_resolve_until_revoked() -> GResult<()> {
+        let tokens = ResumeTokenStore::new(new_state_store(), "demo")?;
+        let envelope: IngressEnvelope = serde_json::from_value(json!({
+            "tenant": "demo",
+            "flow_id": "flow.main",
+            "session_hint": "demo:webhook:approvals:42:user",
+        }))
+        .unwrap();
+        let wait_key = envelope.wait_key();
+        assert!(tokens.token_for(&wait_key)?.is_none());
+
+        let token = new_resume_token();
+        assert_eq!(token.len(), 32);
+        tokens.register(&token, &envelope)?;
+        assert_eq!(tokens.token_for(&wait_key)?, Some(token.clone()));
+        let resolved = tokens.resolve(&token)?.expect("token registered");
+        assert_eq!(resolved.flow_id, "flow.main");
+
+        tokens.revoke(&wait_key)?;
+        assert!(tokens.resolve(&token)?.is_none());
+        assert!(tokens.resolve("unknown")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn tokens_are_claimed_once_and_replaced_per_wait() -> GResult<()> {
+        let tokens = ResumeTokenStore::new(new_state_store(), "demo")?;
+        let envelope: IngressEnvelope = serde_json::from_value(json!({
+            "tenant": "demo",
+            "flow_id": "flow.main",
+            "session_hint": "demo:webhook:approvals:42:user",
+        }))
+        .unwrap();
+
+        let first = new_resume_token();
+        tokens.register(&first, &envelope)?;
+        let second = new_resume_token();
+        tokens.register(&second, &envelope)?;
+        assert!(tokens.resolve(&first)?.is_none());
+
+        assert!(tokens.claim(&second)?.is_some());
+        assert!(tokens.claim(&second)?.is_none());
+        Ok(())
+    }
+}
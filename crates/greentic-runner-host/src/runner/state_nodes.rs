@@ -0,0 +1,130 @@
+//! Built-in `state.get`, `state.set` and `state.delete` node components.
+//!
+//! Values live in the tenant's state store under their own prefix, namespaced by scope so a
+//! user-scoped `theme` never collides with a session-scoped one.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow};
+use greentic_state::{StateKey, StateStore};
+use greentic_types::{EnvId, TenantCtx, TenantId};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::engine::FlowContext;
+use super::errors::{ErrorKind, NodeError};
+
+const STATE_NODE_PREFIX: &str = "flow-state";
+
+/// Who a stored value belongs to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateScope {
+    /// The user behind the activity, across sessions.
+    User,
+    /// The current session.
+    #[default]
+    Session,
+    /// The whole tenant.
+    Tenant,
+}
+
+impl StateScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            StateScope::User => "user",
+            StateScope::Session => "session",
+            StateScope::Tenant => "tenant",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct StatePayload {
+    key: String,
+    #[serde(default)]
+    scope: StateScope,
+    /// Value to store (`state.set`).
+    #[serde(default)]
+    value: Value,
+    /// Returned by `state.get` when nothing is stored.
+    #[serde(default)]
+    default: Value,
+    /// Expiry for `state.set`; without it an existing expiry is kept.
+    #[serde(default)]
+    ttl_secs: Option<u32>,
+}
+
+/// Run the state node `component` with its rendered `payload`.
+pub(crate) fn execute(
+    store: &dyn StateStore,
+    env: &str,
+    ctx: &FlowContext<'_>,
+    component: &str,
+    node_id: &str,
+    payload: Value,
+) -> Result<Value> {
+    let payload: StatePayload = serde_json::from_value(payload)
+        .with_context(|| format!("invalid payload for {component} node {node_id}"))?;
+    if payload.key.is_empty() {
+        return Err(anyhow!(
+            "{component} node {node_id} requires a non-empty key"
+        ));
+    }
+    let key = scoped_key(payload.scope, ctx, &payload.key).ok_or_else(|| {
+        anyhow!(
+            "{component} node {node_id} uses {} scope but the activity has none",
+            payload.scope.as_str()
+        )
+    })?;
+    let key = StateKey::from(key);
+    let tenant = &TenantCtx::new(
+        EnvId::from_str(env).map_err(|err| anyhow!("invalid env id {env}: {err}"))?,
+        TenantId::from_str(ctx.tenant)
+            .map_err(|err| anyhow!("invalid tenant id {}: {err}", ctx.tenant))?,
+    );
+    let store_error = |err| NodeError::new(ErrorKind::Transient, anyhow!("{component}: {err}"));
+    let (key_name, scope) = (payload.key.as_str(), payload.scope.as_str());
+
+    match component {
+        "state.get" => {
+            let stored = store
+                .get_json(tenant, STATE_NODE_PREFIX, &key, None)
+                .map_err(store_error)?;
+            let found = stored.is_some();
+            let value = stored.unwrap_or(payload.default);
+            Ok(json!({ "key": key_name, "scope": scope, "found": found, "value": value }))
+        }
+        "state.set" => {
+            store
+                .set_json(
+                    tenant,
+                    STATE_NODE_PREFIX,
+                    &key,
+                    None,
+                    &payload.value,
+                    payload.ttl_secs,
+                )
+                .map_err(store_error)?;
+            Ok(json!({ "key": key_name, "scope": scope, "value": payload.value }))
+        }
+        "state.delete" => {
+            let deleted = store
+                .del(tenant, STATE_NODE_PREFIX, &key)
+                .map_err(store_error)?;
+            Ok(json!({ "key": key_name, "scope": scope, "deleted": deleted }))
+        }
+        other => Err(anyhow!("{other} is not a state node")),
+    }
+}
+
+/// Store key for `key` in `scope`; `None` when the run lacks the scope's owner.
+fn scoped_key(scope: StateScope, ctx: &FlowContext<'_>, key: &str) -> Option<String> {
+    match scope {
+        StateScope::User => ctx.user_id.map(|user| format!("user/{user}/{key}")),
+        StateScope::Session => ctx
+            .session_id
+            .map(|session| format!("session/{session}/{key}")),
+        StateScope::Tenant => Some(format!("tenant/{key}")),
+    }
+}
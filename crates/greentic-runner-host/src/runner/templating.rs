@@ -0,0 +1,482 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError,
+    RenderErrorReason, ScopedJson, handlebars_helper,
+};
+use serde_json::{Number, Value, json};
+
+/// Names of the helpers every flow gets out of the box.
+pub const BUILTIN_HELPERS: &[&str] = &[
+    "json",
+    "default",
+    "upper",
+    "lower",
+    "capitalize",
+    "truncate",
+    "date",
+    "now",
+    "add",
+    "sub",
+    "mul",
+    "div",
+    "round",
+];
+
+/// Helpers Handlebars itself provides.
+const HANDLEBARS_HELPERS: &[&str] = &[
+    "if", "unless", "each", "with", "lookup", "raw", "log", "eq", "ne", "gt", "gte", "lt", "lte",
+    "and", "or", "not", "len",
+];
+
+/// Whether `name` is taken by a Handlebars or runner built-in helper.
+pub fn is_builtin_helper(name: &str) -> bool {
+    BUILTIN_HELPERS.contains(&name) || HANDLEBARS_HELPERS.contains(&name)
+}
+
+handlebars_helper!(json_helper: |value: Json, {pretty: bool = false}| {
+    let rendered = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    rendered.map_err(|err| RenderErrorReason::Other(format!("json: {err}")))?
+});
+
+handlebars_helper!(default_helper: |*args| {
+    args.iter()
+        .find(|value| !is_blank(value))
+        .map(|value| (*value).clone())
+        .unwrap_or(Value::Null)
+});
+
+handlebars_helper!(upper_helper: |text: str| text.to_uppercase());
+
+handlebars_helper!(lower_helper: |text: str| text.to_lowercase());
+
+handlebars_helper!(capitalize_helper: |text: str| {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+        None => String::new(),
+    }
+});
+
+handlebars_helper!(truncate_helper: |text: str, len: u64, {suffix: str = "..."}| {
+    if text.chars().count() as u64 <= len {
+        text.to_string()
+    } else {
+        let mut cut = text.chars().take(len as usize).collect::<String>();
+        cut.push_str(suffix);
+        cut
+    }
+});
+
+handlebars_helper!(date_helper: |value: Json, {format: str = "%Y-%m-%d"}| {
+    format_time("date", parse_timestamp(value)?, format)?
+});
+
+handlebars_helper!(now_helper: |{format: str = ""}| {
+    let now = Utc::now();
+    if format.is_empty() {
+        now.to_rfc3339_opts(SecondsFormat::Secs, true)
+    } else {
+        format_time("now", now, format)?
+    }
+});
+
+handlebars_helper!(add_helper: |a: Json, b: Json| arithmetic("add", a, b)?);
+handlebars_helper!(sub_helper: |a: Json, b: Json| arithmetic("sub", a, b)?);
+handlebars_helper!(mul_helper: |a: Json, b: Json| arithmetic("mul", a, b)?);
+handlebars_helper!(div_helper: |a: Json, b: Json| arithmetic("div", a, b)?);
+
+handlebars_helper!(round_helper: |value: Json, {digits: u64 = 0}| {
+    let number = as_f64("round", value)?;
+    let factor = 10f64.powi(digits.min(15) as i32);
+    number_value((number * factor).round() / factor)
+});
+
+/// Register the built-in helpers on a fresh template engine.
+pub fn register_builtin_helpers(engine: &mut Handlebars<'static>) {
+    engine.register_helper("json", Box::new(json_helper));
+    engine.register_helper("default", Box::new(default_helper));
+    engine.register_helper("upper", Box::new(upper_helper));
+    engine.register_helper("lower", Box::new(lower_helper));
+    engine.register_helper("capitalize", Box::new(capitalize_helper));
+    engine.register_helper("truncate", Box::new(truncate_helper));
+    engine.register_helper("date", Box::new(date_helper));
+    engine.register_helper("now", Box::new(now_helper));
+    engine.register_helper("add", Box::new(add_helper));
+    engine.register_helper("sub", Box::new(sub_helper));
+    engine.register_helper("mul", Box::new(mul_helper));
+    engine.register_helper("div", Box::new(div_helper));
+    engine.register_helper("round", Box::new(round_helper));
+}
+
+/// Template helpers supplied by the embedding application.
+#[derive(Default, Clone)]
+pub struct HelperRegistry {
+    inner: HashMap<String, Arc<dyn HelperDef + Send + Sync>>,
+}
+
+impl HelperRegistry {
+    pub fn register(&mut self, name: impl Into<String>, helper: Box<dyn HelperDef + Send + Sync>) {
+        self.inner.insert(name.into(), Arc::from(helper));
+    }
+
+    pub fn register_arc(
+        &mut self,
+        name: impl Into<String>,
+        helper: Arc<dyn HelperDef + Send + Sync>,
+    ) {
+        self.inner.insert(name.into(), helper);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.inner.contains_key(name)
+    }
+
+    /// Install every registered helper on `engine`, replacing built-ins of the same name.
+    pub fn install(&self, engine: &mut Handlebars<'static>) {
+        for (name, helper) in &self.inner {
+            engine.register_helper(name, Box::new(SharedHelper(Arc::clone(helper))));
+        }
+    }
+}
+
+struct SharedHelper(Arc<dyn HelperDef + Send + Sync>);
+
+impl HelperDef for SharedHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        self.0.call_inner(h, r, ctx, rc)
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        self.0.call(h, r, ctx, rc, out)
+    }
+}
+
+/// A helper declared in a pack manifest as a template snippet. Positional arguments are
+/// available to the snippet as `args.0`, `args.1`, ... and hash arguments under `hash`.
+pub struct TemplateHelper {
+    name: String,
+    template: String,
+}
+
+impl TemplateHelper {
+    pub fn new(name: impl Into<String>, template: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            template: template.into(),
+        }
+    }
+}
+
+impl HelperDef for TemplateHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let args = h
+            .params()
+            .iter()
+            .map(|param| param.value().clone())
+            .collect::<Vec<_>>();
+        let hash = h
+            .hash()
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.value().clone()))
+            .collect::<BTreeMap<_, _>>();
+        let rendered = r
+            .render_template(&self.template, &json!({ "args": args, "hash": hash }))
+            .map_err(|err| RenderErrorReason::Other(format!("helper {}: {err}", self.name)))?;
+        Ok(ScopedJson::Derived(Value::String(rendered)))
+    }
+}
+
+/// Names referenced as `<prefix>NAME` (e.g. `secrets.API_KEY`) by the templates in `value`.
+pub fn template_references(value: &Value, prefix: &str) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    collect_references(value, prefix, &mut names);
+    names
+}
+
+fn collect_references(value: &Value, prefix: &str, names: &mut BTreeSet<String>) {
+    match value {
+        Value::String(text) if text.contains("{{") => {
+            let is_name = |ch: char| ch.is_ascii_alphanumeric() || ch == '_';
+            for (idx, _) in text.match_indices(prefix) {
+                let preceded = text[..idx]
+                    .chars()
+                    .next_back()
+                    .is_some_and(|ch| is_name(ch) || ch == '.');
+                let rest = &text[idx + prefix.len()..];
+                let name = &rest[..rest.find(|ch| !is_name(ch)).unwrap_or(rest.len())];
+                if !preceded && !name.is_empty() {
+                    names.insert(name.to_string());
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_references(item, prefix, names)),
+        Value::Object(map) => map
+            .values()
+            .for_each(|item| collect_references(item, prefix, names)),
+        _ => {}
+    }
+}
+
+/// Masks resolved secret values wherever they end up in a JSON value.
+#[derive(Default)]
+pub struct Redactor {
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    pub const MASK: &'static str = "[REDACTED]";
+
+    pub fn new(secrets: impl IntoIterator<Item = String>) -> Self {
+        let mut secrets = secrets
+            .into_iter()
+            .filter(|secret| !secret.is_empty())
+            .collect::<Vec<_>>();
+        // Longest first, so a secret containing another is masked whole.
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        Self { secrets }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    pub fn redact(&self, value: Value) -> Value {
+        if self.is_empty() {
+            return value;
+        }
+        match value {
+            Value::String(text) => Value::String(self.secrets.iter().fold(text, |text, secret| {
+                text.replace(secret.as_str(), Self::MASK)
+            })),
+            Value::Array(items) => {
+                Value::Array(items.into_iter().map(|item| self.redact(item)).collect())
+            }
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(key, item)| (key, self.redact(item)))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+}
+
+fn is_blank(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(text) => text.is_empty(),
+        _ => false,
+    }
+}
+
+/// Accept RFC 3339 strings, unix seconds, or `"now"`.
+fn parse_timestamp(value: &Value) -> Result<DateTime<Utc>, RenderErrorReason> {
+    let parsed = match value {
+        Value::String(text) if text == "now" => Some(Utc::now()),
+        Value::String(text) => DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc)),
+        Value::Number(number) => number
+            .as_i64()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0)),
+        _ => None,
+    };
+    parsed.ok_or_else(|| RenderErrorReason::Other(format!("date: cannot parse timestamp {value}")))
+}
+
+/// Format `time` with a strftime pattern from the template. Invalid patterns are render
+/// errors; `DelayedFormat::to_string` would panic on them.
+fn format_time(
+    helper: &str,
+    time: DateTime<Utc>,
+    format: &str,
+) -> Result<String, RenderErrorReason> {
+    let mut out = String::new();
+    write!(out, "{}", time.format(format))
+        .map_err(|_| RenderErrorReason::Other(format!("{helper}: invalid format {format:?}")))?;
+    Ok(out)
+}
+
+fn as_f64(helper: &str, value: &Value) -> Result<f64, RenderErrorReason> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| RenderErrorReason::Other(format!("{helper}: {value} is not a number")))
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(number) => number.as_i64(),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Integer arithmetic when both sides are integers and the result is exact, floats otherwise.
+fn arithmetic(op: &str, a: &Value, b: &Value) -> Result<Value, RenderErrorReason> {
+    if let (Some(x), Some(y)) = (as_i64(a), as_i64(b)) {
+        let exact = match op {
+            "add" => x.checked_add(y),
+            "sub" => x.checked_sub(y),
+            "mul" => x.checked_mul(y),
+            _ => (y != 0 && x % y == 0).then(|| x / y),
+        };
+        if let Some(result) = exact {
+            return Ok(Value::from(result));
+        }
+    }
+    let (x, y) = (as_f64(op, a)?, as_f64(op, b)?);
+    let result = match op {
+        "add" => x + y,
+        "sub" => x - y,
+        "mul" => x * y,
+        _ if y == 0.0 => return Err(RenderErrorReason::Other("div: division by zero".into())),
+        _ => x / y,
+    };
+    Ok(number_value(result))
+}
+
+fn number_value(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        Value::from(value as i64)
+    } else {
+        Number::from_f64(value)
+            .map(Value::Number)
+            .unwrap_or(Value::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine() -> Handlebars<'static> {
+        let mut engine = Handlebars::new();
+        engine.set_strict_mode(false);
+        register_builtin_helpers(&mut engine);
+        engine
+    }
+
+    fn render(template: &str, data: Value) -> String {
+        engine().render_template(template, &data).unwrap()
+    }
+
+    #[test]
+    fn string_and_json_helpers() {
+        let data = json!({ "name": "ada lovelace", "tags": ["a", "b"], "empty": "" });
+        assert_eq!(render("{{upper name}}", data.clone()), "ADA LOVELACE");
+        assert_eq!(render("{{capitalize name}}", data.clone()), "Ada lovelace");
+        assert_eq!(render("{{truncate name 3}}", data.clone()), "ada...");
+        assert_eq!(
+            render("{{truncate name 3 suffix=\"\"}}", data.clone()),
+            "ada"
+        );
+        assert_eq!(render("{{{json tags}}}", data.clone()), r#"["a","b"]"#);
+        assert_eq!(
+            render("{{default empty missing \"n/a\"}}", data.clone()),
+            "n/a"
+        );
+    }
+
+    #[test]
+    fn date_and_math_helpers() {
+        let data = json!({ "ts": "2024-03-05T10:30:00Z", "epoch": 0, "price": "19.99" });
+        assert_eq!(
+            render("{{date ts format=\"%d %b %Y\"}}", data.clone()),
+            "05 Mar 2024"
+        );
+        assert_eq!(render("{{date epoch}}", data.clone()), "1970-01-01");
+        assert_eq!(render("{{add 2 3}}", data.clone()), "5");
+        assert_eq!(render("{{div 7 2}}", data.clone()), "3.5");
+        assert_eq!(
+            render("{{round (mul price 3) digits=1}}", data.clone()),
+            "60"
+        );
+        assert!(engine().render_template("{{div 1 0}}", &data).is_err());
+    }
+
+    #[test]
+    fn invalid_date_formats_are_render_errors() {
+        let data = json!({ "ts": "2024-03-05T10:30:00Z" });
+        let err = engine()
+            .render_template("{{date ts format=\"%Q\"}}", &data)
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid format"));
+        assert!(
+            engine()
+                .render_template("{{now format=\"%Y %Q\"}}", &data)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn references_are_found_and_secrets_redacted() {
+        let payload = json!({
+            "headers": { "authorization": "Bearer {{secrets.API_TOKEN}}" },
+            "url": "{{env.BASE_URL}}/v1?key={{ secrets.API_TOKEN }}",
+            "note": "secrets.PLAIN is not a template",
+            "other": "{{nodes.env.payload}} {{myenv.X}}",
+        });
+        assert_eq!(
+            template_references(&payload, "secrets."),
+            BTreeSet::from(["API_TOKEN".to_string()])
+        );
+        assert_eq!(
+            template_references(&payload, "env."),
+            BTreeSet::from(["BASE_URL".to_string()])
+        );
+
+        let redactor = Redactor::new(["s3cr3t".to_string()]);
+        assert_eq!(
+            redactor.redact(json!({ "text": "token s3cr3t", "n": [1, "s3cr3t"] })),
+            json!({ "text": "token [REDACTED]", "n": [1, "[REDACTED]"] })
+        );
+    }
+
+    #[test]
+    fn template_helpers_see_args_and_hash() {
+        let mut engine = engine();
+        engine.register_helper(
+            "greet",
+            Box::new(TemplateHelper::new(
+                "greet",
+                "{{hash.greeting}}, {{capitalize args.0}}!",
+            )),
+        );
+        let out = engine
+            .render_template("{{greet name greeting=\"Hi\"}}", &json!({ "name": "ada" }))
+            .unwrap();
+        assert_eq!(out, "Hi, Ada!");
+    }
+}
@@ -0,0 +1,446 @@
+//! Durable wake-ups for `session.wait` nodes that set `timeout_ms`.
+//!
+//! Each pending wake-up is its own record in the tenant's state store, so wake-ups survive
+//! restarts and pack reloads. A small index lists the armed wake-up of every waiting flow so
+//! the poller can find them; the records are the source of truth. A poller claims a due
+//! wake-up by deleting its record, which only one host can do, and then resumes the flow
+//! through its timeout route.
+//!
+//! The index is rewritten as a whole. Within a host the rewrites are serialised by a lock
+//! that reloads hand over to the replacing runtime. Hosts sharing a state store can still
+//! overwrite each other's index update; each host remembers the wake-ups it armed and puts
+//! back the ones whose record survives on its next poll, so a dropped entry only stays lost
+//! if the host that armed it goes away first.
+//!
+//! Wake-ups fire at most once: a host that stops between claiming a wake-up and resuming
+//! the flow loses it. The flow keeps waiting and can still be resumed by an activity or its
+//! resume token, but its timeout route will not run.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use greentic_state::StateKey;
+use greentic_types::TenantCtx;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+use super::adapt_messaging::send_replies;
+use super::engine::{egress_channel, unix_millis};
+use crate::engine::error::{GResult, RunnerError};
+use crate::engine::runtime::IngressEnvelope;
+use crate::runtime::TenantRuntime;
+use crate::storage::state::{DynStateStore, host_tenant_ctx, map_state_error};
+
+const WAKEUP_PREFIX: &str = "session-wait";
+const WAKEUP_INDEX: &str = "wakeups";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A waiting flow and the moment its `session.wait` expires.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingWakeup {
+    /// Unix time in milliseconds.
+    pub wake_at_ms: u64,
+    /// Envelope of the activity that left the flow waiting; used to find it again.
+    pub envelope: IngressEnvelope,
+}
+
+/// Pending wake-ups, one record per wake-up, indexed by [`IngressEnvelope::wait_key`].
+#[derive(Clone)]
+pub struct WaitTimeoutStore {
+    store: DynStateStore,
+    tenant: TenantCtx,
+    /// Wake-ups armed through this store, by wait key. Also serialises index updates.
+    armed: Arc<Mutex<BTreeMap<String, u64>>>,
+}
+
+impl WaitTimeoutStore {
+    pub fn new(store: DynStateStore, tenant: &str) -> GResult<Self> {
+        Ok(Self {
+            store,
+            tenant: host_tenant_ctx(tenant)?,
+            armed: Arc::new(Mutex::new(BTreeMap::new())),
+        })
+    }
+
+    /// Schedule a wake-up for the waiting flow of `envelope`, replacing any earlier one.
+    pub fn arm(&self, envelope: &IngressEnvelope, wake_at_ms: u64) -> GResult<()> {
+        let wait_key = envelope.wait_key();
+        let wakeup = PendingWakeup {
+            wake_at_ms,
+            envelope: envelope.clone(),
+        };
+        let value = serde_json::to_value(&wakeup).map_err(|err| RunnerError::State {
+            reason: format!("failed to encode wake-up: {err}"),
+        })?;
+        self.store
+            .set_json(
+                &self.tenant,
+                WAKEUP_PREFIX,
+                &record_key(&wait_key, wake_at_ms),
+                None,
+                &value,
+                None,
+            )
+            .map_err(map_state_error)?;
+
+        let mut armed = self.armed.lock();
+        let mut index = self.load_index()?;
+        if let Some(previous) = index.insert(wait_key.clone(), wake_at_ms)
+            && previous != wake_at_ms
+        {
+            self.delete_record(&wait_key, previous)?;
+        }
+        self.persist_index(&index)?;
+        armed.insert(wait_key, wake_at_ms);
+        Ok(())
+    }
+
+    /// Drop the wake-up filed under `wait_key`. With `wake_at_ms` set, only a wake-up due at
+    /// exactly that time is dropped, so one armed in the meantime survives.
+    pub fn disarm(&self, wait_key: &str, wake_at_ms: Option<u64>) -> GResult<()> {
+        let mut armed = self.armed.lock();
+        let local = armed.get(wait_key).copied();
+        if local.is_some() && wake_at_ms.is_none_or(|at| Some(at) == local) {
+            armed.remove(wait_key);
+        }
+        let mut index = self.load_index()?;
+        let Some(&indexed) = index.get(wait_key) else {
+            if let Some(at) = wake_at_ms.or(local) {
+                self.delete_record(wait_key, at)?;
+            }
+            return Ok(());
+        };
+        if let Some(at) = wake_at_ms
+            && at != indexed
+        {
+            self.delete_record(wait_key, at)?;
+            return Ok(());
+        }
+        self.delete_record(wait_key, indexed)?;
+        index.remove(wait_key);
+        self.persist_index(&index)
+    }
+
+    /// Take ownership of `wakeup` by deleting its record. Only one caller, across every host
+    /// sharing the state store, gets `true`; the others must not resume the flow.
+    pub fn claim(&self, wakeup: &PendingWakeup) -> GResult<bool> {
+        self.delete_record(&wakeup.envelope.wait_key(), wakeup.wake_at_ms)
+    }
+
+    pub fn pending(&self) -> GResult<BTreeMap<String, PendingWakeup>> {
+        let index = self.repair_index()?;
+        let mut pending = BTreeMap::new();
+        for (wait_key, wake_at_ms) in index {
+            if let Some(wakeup) = self.load_record(&wait_key, wake_at_ms)? {
+                pending.insert(wait_key, wakeup);
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Wake-ups due at or before `now_ms`, earliest first.
+    pub fn due(&self, now_ms: u64) -> GResult<Vec<PendingWakeup>> {
+        let mut due = self
+            .pending()?
+            .into_values()
+            .filter(|wakeup| wakeup.wake_at_ms <= now_ms)
+            .collect::<Vec<_>>();
+        due.sort_by_key(|wakeup| wakeup.wake_at_ms);
+        Ok(due)
+    }
+
+    /// The index, with the wake-ups this store armed put back if another host's index update
+    /// dropped them while their record survives.
+    fn repair_index(&self) -> GResult<BTreeMap<String, u64>> {
+        let mut armed = self.armed.lock();
+        let mut index = self.load_index()?;
+        let mut repaired = false;
+        for (wait_key, wake_at_ms) in armed.clone() {
+            match index.get(&wait_key) {
+                Some(&indexed) if indexed == wake_at_ms => continue,
+                None if self.load_record(&wait_key, wake_at_ms)?.is_some() => {
+                    index.insert(wait_key, wake_at_ms);
+                    repaired = true;
+                }
+                // Re-armed, disarmed or claimed elsewhere.
+                _ => {
+                    armed.remove(&wait_key);
+                }
+            }
+        }
+        if repaired {
+            self.persist_index(&index)?;
+        }
+        Ok(index)
+    }
+
+    fn load_record(&self, wait_key: &str, wake_at_ms: u64) -> GResult<Option<PendingWakeup>> {
+        let value = self
+            .store
+            .get_json(
+                &self.tenant,
+                WAKEUP_PREFIX,
+                &record_key(wait_key, wake_at_ms),
+                None,
+            )
+            .map_err(map_state_error)?;
+        value
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|err| RunnerError::State {
+                reason: format!("failed to decode wake-up: {err}"),
+            })
+    }
+
+    fn delete_record(&self, wait_key: &str, wake_at_ms: u64) -> GResult<bool> {
+        self.store
+            .del(
+                &self.tenant,
+                WAKEUP_PREFIX,
+                &record_key(wait_key, wake_at_ms),
+            )
+            .map_err(map_state_error)
+    }
+
+    fn load_index(&self) -> GResult<BTreeMap<String, u64>> {
+        let value = self
+            .store
+            .get_json(&self.tenant, WAKEUP_PREFIX, &index_key(), None)
+            .map_err(map_state_error)?;
+        let Some(value) = value else {
+            return Ok(BTreeMap::new());
+        };
+        serde_json::from_value(value).map_err(|err| RunnerError::State {
+            reason: format!("failed to decode wake-up index: {err}"),
+        })
+    }
+
+    fn persist_index(&self, index: &BTreeMap<String, u64>) -> GResult<()> {
+        if index.is_empty() {
+            self.store
+                .del(&self.tenant, WAKEUP_PREFIX, &index_key())
+                .map_err(map_state_error)?;
+            return Ok(());
+        }
+        let value = serde_json::to_value(index).map_err(|err| RunnerError::State {
+            reason: format!("failed to encode wake-up index: {err}"),
+        })?;
+        self.store
+            .set_json(
+                &self.tenant,
+                WAKEUP_PREFIX,
+                &index_key(),
+                None,
+                &value,
+                None,
+            )
+            .map_err(map_state_error)
+    }
+}
+
+/// Poll the tenant's pending wake-ups and resume expired waits. Replies of a resumed flow
+/// go out through the adapter of the conversation that left it waiting. The task stops once
+/// the runtime is dropped; its replacement picks the wake-ups up from the store.
+pub fn spawn_wait_timeouts(runtime: &Arc<TenantRuntime>) -> JoinHandle<()> {
+    let runtime: Weak<TenantRuntime> = Arc::downgrade(runtime);
+    tokio::spawn(async move {
+        loop {
+            sleep(POLL_INTERVAL).await;
+            let Some(runtime) = runtime.upgrade() else {
+                break;
+            };
+            let store = runtime.wait_timeouts();
+            let due = match store.due(unix_millis()) {
+                Ok(due) => due,
+                Err(err) => {
+                    tracing::warn!(error = %err, "failed to read pending wake-ups");
+                    continue;
+                }
+            };
+            for wakeup in due {
+                let session = wakeup.envelope.session_key();
+                match store.claim(&wakeup) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        tracing::debug!(session = %session, "wake-up claimed elsewhere");
+                        continue;
+                    }
+                    Err(err) => {
+                        tracing::warn!(session = %session, error = %err, "failed to claim wake-up");
+                        continue;
+                    }
+                }
+                resume_expired(&runtime, &wakeup).await;
+                if let Err(err) = store.disarm(&wakeup.envelope.wait_key(), Some(wakeup.wake_at_ms))
+                {
+                    tracing::warn!(session = %session, error = %err, "failed to clear wake-up");
+                }
+            }
+        }
+    })
+}
+
+async fn resume_expired(runtime: &Arc<TenantRuntime>, wakeup: &PendingWakeup) {
+    let session = wakeup.envelope.session_key();
+    let flow_id = &wakeup.envelope.flow_id;
+    let (egress, mut emitted) = egress_channel();
+    let streamer = {
+        let runtime = Arc::clone(runtime);
+        let envelope = wakeup.envelope.clone();
+        tokio::spawn(async move {
+            while let Some(item) = emitted.recv().await {
+                deliver(&runtime, &envelope, &item.payload).await;
+            }
+        })
+    };
+    let result = runtime
+        .state_machine()
+        .resume_expired_streaming(wakeup, egress)
+        .await;
+    if let Err(err) = streamer.await {
+        tracing::error!(session = %session, error = %err, "wake-up reply task failed");
+    }
+    match result {
+        Ok(Some(output)) => {
+            deliver(runtime, &wakeup.envelope, &output).await;
+            tracing::info!(
+                flow_id = %flow_id,
+                session = %session,
+                "resumed flow after session.wait timeout"
+            );
+        }
+        Ok(None) => {
+            tracing::debug!(
+                flow_id = %flow_id,
+                session = %session,
+                "skipping stale session.wait wake-up"
+            );
+        }
+        Err(err) => {
+            let chain = err.chain().map(|e| e.to_string()).collect::<Vec<_>>();
+            tracing::error!(
+                flow_id = %flow_id,
+                session = %session,
+                error.cause_chain = ?chain,
+                "session.wait timeout resume failed"
+            );
+        }
+    }
+}
+
+async fn deliver(runtime: &TenantRuntime, envelope: &IngressEnvelope, payload: &Value) {
+    if let Err(err) = send_replies(runtime, envelope, payload).await {
+        tracing::warn!(
+            flow_id = %envelope.flow_id,
+            session = %envelope.session_key(),
+            error = %err,
+            "failed to deliver session.wait timeout reply"
+        );
+    }
+}
+
+fn record_key(wait_key: &str, wake_at_ms: u64) -> StateKey {
+    StateKey::from(format!("{wait_key}@{wake_at_ms}"))
+}
+
+fn index_key() -> StateKey {
+    StateKey::from(WAKEUP_INDEX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::state::new_state_store;
+    use serde_json::{Value, json};
+
+    fn envelope(session: &str, flow_id: &str) -> IngressEnvelope {
+        serde_json::from_value::<IngressEnvelope>(json!({
+            "tenant": "demo",
+            "flow_id": flow_id,
+            "session_hint": session,
+            "payload": Value::Null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn wakeups_become_due_and_can_be_disarmed() -> GResult<()> {
+        let store = WaitTimeoutStore::new(new_state_store(), "demo")?;
+        let a = envelope("a", "flow.main");
+        let b = envelope("b", "flow.main");
+        store.arm(&a, 1_000)?;
+        store.arm(&b, 5_000)?;
+        assert!(store.due(999)?.is_empty());
+        let due = store.due(1_000)?;
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].envelope.session_hint.as_deref(), Some("a"));
+
+        // A wake-up re-armed in the meantime is kept.
+        store.arm(&a, 2_000)?;
+        store.disarm(&a.wait_key(), Some(1_000))?;
+        assert_eq!(store.pending()?[&a.wait_key()].wake_at_ms, 2_000);
+
+        // Another flow of the same session waits on its own wake-up.
+        let other = envelope("a", "flow.other");
+        store.arm(&other, 3_000)?;
+        assert_eq!(store.pending()?.len(), 3);
+
+        store.disarm(&a.wait_key(), None)?;
+        store.disarm(&other.wait_key(), None)?;
+        store.disarm(&b.wait_key(), None)?;
+        assert!(store.pending()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn a_wakeup_is_claimed_once() -> GResult<()> {
+        let state = new_state_store();
+        let first = WaitTimeoutStore::new(Arc::clone(&state), "demo")?;
+        let second = WaitTimeoutStore::new(state, "demo")?;
+        let a = envelope("a", "flow.main");
+        first.arm(&a, 1_000)?;
+        let due = second.due(1_000)?;
+        assert_eq!(due.len(), 1);
+        assert!(second.claim(&due[0])?);
+        assert!(!first.claim(&due[0])?);
+
+        // Re-arming replaces the record, so the old wake-up can no longer be claimed.
+        first.arm(&a, 2_000)?;
+        first.arm(&a, 3_000)?;
+        let stale = PendingWakeup {
+            wake_at_ms: 2_000,
+            envelope: a.clone(),
+        };
+        assert!(!first.claim(&stale)?);
+        assert_eq!(second.due(3_000)?[0].wake_at_ms, 3_000);
+        Ok(())
+    }
+
+    #[test]
+    fn wakeups_lost_to_a_concurrent_index_update_are_restored() -> GResult<()> {
+        let state = new_state_store();
+        let first = WaitTimeoutStore::new(Arc::clone(&state), "demo")?;
+        let second = WaitTimeoutStore::new(state, "demo")?;
+        let a = envelope("a", "flow.main");
+        let b = envelope("b", "flow.main");
+        first.arm(&a, 1_000)?;
+        second.arm(&b, 2_000)?;
+        // The second host read the index before the first one wrote it, so its write drops `a`.
+        second.persist_index(&BTreeMap::from([(b.wait_key(), 2_000)]))?;
+        assert_eq!(second.pending()?.len(), 1);
+
+        // The first host's next poll puts its wake-up back for everyone.
+        assert_eq!(first.due(5_000)?.len(), 2);
+        assert_eq!(second.pending()?.len(), 2);
+
+        // A wake-up disarmed elsewhere is not brought back.
+        second.disarm(&a.wait_key(), None)?;
+        assert_eq!(first.pending()?.len(), 1);
+        assert!(first.armed.lock().is_empty());
+        Ok(())
+    }
+}
@@ -4,21 +4,24 @@ use std::path::Path;
 use std::sync::Arc;
 use std::time::Instant;
 
-use anyhow::{Context, Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use arc_swap::ArcSwap;
 use axum::http::StatusCode;
 use lru::LruCache;
 use parking_lot::Mutex;
 use reqwest::Client;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use tokio::task::JoinHandle;
 
 use crate::config::HostConfig;
 use crate::engine::host::{SessionHost, StateHost};
 use crate::engine::runtime::StateMachineRuntime;
 use crate::pack::PackRuntime;
-use crate::runner::engine::FlowEngine;
+use crate::runner::engine::{EngineExtensions, FlowEngine};
 use crate::runner::mocks::MockLayer;
+use crate::runner::resume_tokens::ResumeTokenStore;
+use crate::runner::wait_timeouts::WaitTimeoutStore;
 use crate::storage::session::DynSessionStore;
 use crate::storage::state::DynStateStore;
 use crate::wasi::RunnerWasiPolicy;
@@ -78,6 +81,7 @@ pub struct TenantRuntime {
     webhook_cache: Mutex<LruCache<String, Value>>,
     messaging_rate: Mutex<RateLimiter>,
     mocks: Option<Arc<MockLayer>>,
+    wait_timeouts: WaitTimeoutStore,
     timer_handles: Mutex<Vec<JoinHandle<()>>>,
 }
 
@@ -94,6 +98,7 @@ impl TenantRuntime {
         session_store: DynSessionStore,
         state_store: DynStateStore,
         state_host: Arc<dyn StateHost>,
+        extensions: EngineExtensions,
     ) -> Result<Arc<Self>> {
         let pack = Arc::new(
             PackRuntime::load(
@@ -118,23 +123,31 @@ impl TenantRuntime {
         Self::from_packs(
             config,
             vec![(pack, digest)],
+            None,
             mocks,
             session_host,
             session_store,
             state_store,
             state_host,
+            extensions,
         )
         .await
     }
 
+    /// Build a runtime from loaded packs, main pack first. Pass the runtime this one replaces
+    /// as `previous` so sessions waiting on its packs keep resuming there and its runs in
+    /// flight can still be cancelled.
+    #[allow(clippy::too_many_arguments)]
     pub async fn from_packs(
         config: Arc<HostConfig>,
         packs: Vec<(Arc<PackRuntime>, Option<String>)>,
+        previous: Option<&TenantRuntime>,
         mocks: Option<Arc<MockLayer>>,
         session_host: Arc<dyn SessionHost>,
         session_store: DynSessionStore,
-        _state_store: DynStateStore,
+        state_store: DynStateStore,
         state_host: Arc<dyn StateHost>,
+        extensions: EngineExtensions,
     ) -> Result<Arc<Self>> {
         let telegram_capacity = NonZeroUsize::new(TELEGRAM_CACHE_CAPACITY)
             .expect("telegram cache capacity must be > 0");
@@ -149,17 +162,35 @@ impl TenantRuntime {
             .map(|(_, digest)| digest.clone())
             .collect::<Vec<_>>();
         let engine = Arc::new(
-            FlowEngine::new(pack_runtimes.clone(), Arc::clone(&config))
+            FlowEngine::with_extensions(pack_runtimes.clone(), Arc::clone(&config), extensions)
                 .await
-                .context("failed to prime flow engine")?,
+                .context("failed to prime flow engine")?
+                .with_state_store(Arc::clone(&state_store)),
         );
+        // Runs still in flight on the previous runtime update the same wake-up index.
+        let wait_timeouts = match previous {
+            Some(previous) => previous.wait_timeouts.clone(),
+            None => WaitTimeoutStore::new(Arc::clone(&state_store), &config.tenant)
+                .map_err(|err| anyhow!("failed to open session.wait timeouts: {err}"))?,
+        };
+        let resume_tokens = ResumeTokenStore::new(state_store, &config.tenant)
+            .map_err(|err| anyhow!("failed to open resume tokens: {err}"))?;
         let state_machine = Arc::new(
             StateMachineRuntime::from_flow_engine(
                 Arc::clone(&config),
                 Arc::clone(&engine),
+                pack_set_digest(&digests),
+                previous
+                    .map(|previous| previous.state_machine.pinned_engines())
+                    .unwrap_or_default(),
+                previous
+                    .map(|previous| previous.state_machine.active_runs())
+                    .unwrap_or_default(),
                 session_host,
                 session_store,
                 state_host,
+                wait_timeouts.clone(),
+                resume_tokens,
                 mocks.clone(),
             )
             .context("failed to initialise state machine runtime")?,
@@ -181,6 +212,7 @@ impl TenantRuntime {
                 rate_limits.messaging_burst,
             )),
             mocks,
+            wait_timeouts,
             timer_handles: Mutex::new(Vec::new()),
         }))
     }
@@ -243,6 +275,11 @@ impl TenantRuntime {
         self.mocks.as_ref()
     }
 
+    /// Pending wake-ups of `session.wait` nodes with a timeout.
+    pub fn wait_timeouts(&self) -> &WaitTimeoutStore {
+        &self.wait_timeouts
+    }
+
     pub fn register_timers(&self, handles: Vec<JoinHandle<()>>) {
         self.timer_handles.lock().extend(handles);
     }
@@ -301,3 +338,20 @@ impl RateLimiter {
         }
     }
 }
+
+/// Digest identifying the loaded flows: the main pack's digest, or a digest over the main
+/// pack and every overlay, in load order. `None` when any of them was loaded without one.
+fn pack_set_digest(digests: &[Option<String>]) -> Option<String> {
+    match digests {
+        [] => None,
+        [main] => main.clone(),
+        _ => {
+            let mut hasher = Sha256::new();
+            for digest in digests {
+                hasher.update(digest.as_deref()?.as_bytes());
+                hasher.update(b"\n");
+            }
+            Some(format!("sha256:{}", hex::encode(hasher.finalize())))
+        }
+    }
+}
@@ -1,4 +1,4 @@
 #![allow(dead_code)]
 
 pub use wasmtime::component::{Component, Linker, ResourceTable};
-pub use wasmtime::{Engine, Result as WasmResult, Store};
+pub use wasmtime::{Config, Engine, Result as WasmResult, Store, UpdateDeadline};
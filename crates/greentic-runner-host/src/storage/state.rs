@@ -9,6 +9,7 @@ use serde_json::Value;
 
 use crate::engine::error::{GResult, RunnerError};
 use crate::engine::host::{SessionKey, StateHost};
+use crate::engine::runtime::DEFAULT_ENV;
 
 pub type DynStateStore = Arc<dyn StateStore>;
 
@@ -81,12 +82,19 @@ fn tenant_ctx_from_key(key: &SessionKey) -> GResult<TenantCtx> {
     Ok(TenantCtx::new(env_id, tenant_id))
 }
 
+/// Tenant context for host-owned records (wake-ups, resume tokens) of `tenant`.
+pub(crate) fn host_tenant_ctx(tenant: &str) -> GResult<TenantCtx> {
+    let env = EnvId::from_str(DEFAULT_ENV).map_err(map_state_error)?;
+    let tenant_id = TenantId::from_str(tenant).map_err(map_state_error)?;
+    Ok(TenantCtx::new(env, tenant_id))
+}
+
 fn derive_state_key(key: &SessionKey) -> StoreStateKey {
     let hint = key.session_hint.as_deref().unwrap_or("-");
     StoreStateKey::from(format!("{}::{hint}", key.flow_id))
 }
 
-fn map_state_error(err: greentic_types::GreenticError) -> RunnerError {
+pub(crate) fn map_state_error(err: greentic_types::GreenticError) -> RunnerError {
     RunnerError::State {
         reason: err.to_string(),
     }
@@ -13,6 +13,7 @@ use crate::host::RunnerHost;
 use crate::http::health::HealthState;
 use crate::pack::PackRuntime;
 use crate::runner::adapt_timer;
+use crate::runner::engine::EngineExtensions;
 use crate::runtime::{ActivePacks, TenantRuntime};
 use crate::storage::session::DynSessionStore;
 use crate::storage::state::DynStateStore;
@@ -60,6 +61,7 @@ pub async fn start_pack_watcher(
     let state_store = host.state_store();
     let state_host = host.state_host();
     let wasi_policy = host.wasi_policy();
+    let extensions = host.engine_extensions();
 
     reload_once(
         configs.as_ref(),
@@ -72,6 +74,7 @@ pub async fn start_pack_watcher(
         state_store.clone(),
         state_host.clone(),
         Arc::clone(&wasi_policy),
+        extensions.clone(),
     )
     .await?;
 
@@ -105,6 +108,7 @@ pub async fn start_pack_watcher(
                 state_store_clone.clone(),
                 state_host.clone(),
                 Arc::clone(&wasi_policy_clone),
+                extensions.clone(),
             )
             .await
             {
@@ -131,6 +135,7 @@ async fn reload_once(
     state_store: DynStateStore,
     state_host: Arc<dyn StateHost>,
     wasi_policy: Arc<RunnerWasiPolicy>,
+    extensions: EngineExtensions,
 ) -> Result<()> {
     let index = Index::load(&cfg.index_location)?;
     let resolved = manager.resolve_all_for_index(&index)?;
@@ -180,14 +185,17 @@ async fn reload_once(
             packs.push((runtime, Some(overlay.digest.as_str().to_string())));
         }
 
+        let previous = active.load(tenant);
         let runtime = TenantRuntime::from_packs(
             Arc::clone(&config),
             packs,
+            previous.as_deref(),
             None,
             Arc::clone(&session_host),
             Arc::clone(&session_store),
             Arc::clone(&state_store),
             Arc::clone(&state_host),
+            extensions.clone(),
         )
         .await?;
         let timers = adapt_timer::spawn_timers(Arc::clone(&runtime))?;
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use greentic_runner::desktop::debug::DebugSession;
+use greentic_runner::desktop::{RunStatus, desktop_defaults, run_pack_with_options};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "greentic-run-pack",
+    about = "Run a pack locally with the desktop runner"
+)]
+struct Cli {
+    /// Pack archive to run
+    #[arg(value_name = "PACK")]
+    pack: PathBuf,
+
+    /// Flow to start instead of the pack's entry flow
+    #[arg(long, value_name = "FLOW")]
+    flow: Option<String>,
+
+    /// Flow input as JSON
+    #[arg(long, value_name = "JSON", default_value = "{}")]
+    input: String,
+
+    /// Directory for the run's artifacts
+    #[arg(long, value_name = "DIR")]
+    artifacts_dir: Option<PathBuf>,
+
+    /// Record the run's journal into `journal.json` in the artifacts directory
+    #[arg(long)]
+    record_journal: bool,
+
+    /// Replay the `journal.json` of an earlier run instead of dispatching nodes
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Attach the step debugger: JSON commands on stdin, JSON events on stdout
+    #[arg(long)]
+    debug: bool,
+
+    /// Pause before the first node (implies --debug)
+    #[arg(long)]
+    stop_on_entry: bool,
+
+    /// Pause before this node id; repeat for more (implies --debug)
+    #[arg(long = "break", value_name = "NODE")]
+    breakpoints: Vec<String>,
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let mut opts = desktop_defaults();
+    opts.entry_flow = cli.flow;
+    opts.input = serde_json::from_str(&cli.input).context("--input is not valid JSON")?;
+    opts.artifacts_dir = cli.artifacts_dir;
+    opts.record_journal = cli.record_journal;
+    opts.replay = cli.replay;
+    let debugging = cli.debug || cli.stop_on_entry || !cli.breakpoints.is_empty();
+    if debugging {
+        opts.debugger = Some(DebugSession::stdio(cli.stop_on_entry, cli.breakpoints));
+    }
+
+    let result = run_pack_with_options(&cli.pack, opts)?;
+    let summary = serde_json::to_string_pretty(&result)?;
+    // Debug events own stdout while a debugger is attached.
+    if debugging {
+        eprintln!("{summary}");
+    } else {
+        println!("{summary}");
+    }
+    Ok(match result.status {
+        RunStatus::Failure => ExitCode::FAILURE,
+        RunStatus::Success | RunStatus::PartialFailure => ExitCode::SUCCESS,
+    })
+}